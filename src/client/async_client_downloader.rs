@@ -0,0 +1,623 @@
+use reqwest::Client;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::ClientDownloaderError;
+use crate::json_profiles::ProfileJson;
+use crate::launcher_manifest::{FabricLoaderManifest, LauncherManifest, LauncherManifestVersion};
+use crate::manifest::{
+    manifest_from_fabric, manifest_from_forge, manifest_from_neoforge, maven_to_path,
+    processor_libraries, AssetIndex, FabricManifest, Manifest, VersionType,
+};
+
+use super::client_downloader::{
+    cached_manifest_up_to_date, check_manifest_checksum, check_results, copy_virtual_assets,
+    loader_version_key, parent_dir, read_installer, server_download, version_jar_path,
+    DownloadOptions, CONNECT_TIMEOUT, REQUEST_TIMEOUT, USER_AGENT, VERSION_MANIFEST_URL,
+};
+use super::downloader::report;
+use super::{
+    DownloadOutput, DownloadResult, ForgeInstaller, InstalledVersion, Launcher, Metadata,
+    MirrorConfig, PathStrategy, Progress, ProgressEvent,
+};
+
+/// The client used when none is given, set up like the one of the blocking
+/// downloader.
+fn client_builder() -> reqwest::ClientBuilder {
+    Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+}
+
+/// Non-blocking counterpart of `ClientDownloader`, for callers that already
+/// run inside an async runtime.
+#[derive(Debug)]
+pub struct AsyncClientDownloader {
+    pub main_manifest: LauncherManifest,
+    client: Client,
+    options: DownloadOptions,
+    profiles_json: bool,
+}
+
+impl AsyncClientDownloader {
+    pub async fn new() -> Result<Self, ClientDownloaderError> {
+        Self::new_from_url(VERSION_MANIFEST_URL).await
+    }
+
+    /// Same as `new`, but the version manifest is fetched from `url`.
+    pub async fn new_from_url(url: &str) -> Result<Self, ClientDownloaderError> {
+        Self::build(client_builder().build()?, url).await
+    }
+
+    /// Same as `new`, but every request, including the file downloads, goes
+    /// through the proxy at `url`.
+    pub async fn with_proxy(url: &str) -> Result<Self, ClientDownloaderError> {
+        let proxy = reqwest::Proxy::all(url)?;
+        let client = client_builder().proxy(proxy.clone()).build()?;
+
+        let mut downloader = Self::build(client, VERSION_MANIFEST_URL).await?;
+        downloader.options.proxy = Some(proxy);
+        Ok(downloader)
+    }
+
+    async fn build(client: Client, url: &str) -> Result<Self, ClientDownloaderError> {
+        Ok(Self {
+            main_manifest: Self::init_from_url(&client, url).await?,
+            client,
+            options: DownloadOptions::default(),
+            profiles_json: false,
+        })
+    }
+
+    /// Makes `download_version` add a profile for the version to the
+    /// `launcher_profiles.json` of the game directory.
+    pub fn with_profiles_json(&mut self, profiles_json: bool) -> &mut Self {
+        self.profiles_json = profiles_json;
+        self
+    }
+
+    /// See `ClientDownloader::with_mappings`.
+    pub fn with_mappings(&mut self, include_mappings: bool) -> &mut Self {
+        self.options.mappings = include_mappings;
+        self
+    }
+
+    /// See `ClientDownloader::with_mirror`.
+    pub fn with_mirror(&mut self, mirror: MirrorConfig) -> &mut Self {
+        self.options.mirror = mirror;
+        self
+    }
+
+    /// See `ClientDownloader::with_path_strategy`.
+    pub fn with_path_strategy(&mut self, paths: impl PathStrategy + 'static) -> &mut Self {
+        self.options.paths = Arc::new(paths);
+        self
+    }
+
+    /// See `ClientDownloader::with_assets_path`.
+    pub fn with_assets_path(&mut self, assets_path: PathBuf) -> &mut Self {
+        self.options.assets_path = Some(assets_path);
+        self
+    }
+
+    /// See `ClientDownloader::with_libraries_path`.
+    pub fn with_libraries_path(&mut self, libraries_path: PathBuf) -> &mut Self {
+        self.options.libraries_path = Some(libraries_path);
+        self
+    }
+
+    /// Keeps the version manifests fetched by `get_manifest` in
+    /// `cache_dir`, and reads them from there while they are up to date.
+    pub fn with_manifest_cache(&mut self, cache_dir: PathBuf) -> &mut Self {
+        self.options.manifest_cache = Some(cache_dir);
+        self
+    }
+
+    /// See `ClientDownloader::with_excluded_libraries`.
+    pub fn with_excluded_libraries(&mut self, names: Vec<String>) -> &mut Self {
+        self.options.excluded_libraries = names;
+        self
+    }
+
+    pub async fn init(client: &Client) -> Result<LauncherManifest, ClientDownloaderError> {
+        Self::init_from_url(client, VERSION_MANIFEST_URL).await
+    }
+
+    /// Same as `init`, but the version manifest is fetched from `url`.
+    pub async fn init_from_url(
+        client: &Client,
+        url: &str,
+    ) -> Result<LauncherManifest, ClientDownloaderError> {
+        let response = client.get(url).send().await?.error_for_status()?;
+
+        let data: LauncherManifest = response.json().await?;
+        Ok(data)
+    }
+
+    pub fn get_list_versions(&self) -> Vec<LauncherManifestVersion> {
+        self.main_manifest.versions.clone()
+    }
+
+    /// The versions sorted by release time. The times are ISO-8601 in UTC,
+    /// so they sort as strings.
+    pub fn get_list_versions_sorted(&self, newest_first: bool) -> Vec<LauncherManifestVersion> {
+        let mut versions = self.main_manifest.versions.clone();
+        versions.sort_by(|a, b| a.release_time.cmp(&b.release_time));
+        if newest_first {
+            versions.reverse();
+        }
+        versions
+    }
+
+    /// The `n` most recently released versions, newest first.
+    pub fn recent_versions(&self, n: usize) -> Vec<LauncherManifestVersion> {
+        let mut versions = self.get_list_versions_sorted(true);
+        versions.truncate(n);
+        versions
+    }
+
+    /// The versions whose type is one of `types`, e.g. only the releases.
+    pub fn get_list_versions_filtered(
+        &self,
+        types: &[VersionType],
+    ) -> Vec<LauncherManifestVersion> {
+        self.main_manifest
+            .versions
+            .iter()
+            .filter(|v| types.contains(&v.version_type))
+            .cloned()
+            .collect()
+    }
+
+    pub async fn get_list_fabric_loader_versions(
+        &self,
+        game_version: &str,
+    ) -> Result<Vec<FabricLoaderManifest>, ClientDownloaderError> {
+        let response = self
+            .client
+            .get(self.options.mirror.rewrite(&format!(
+                "https://meta.fabricmc.net/v2/versions/loader/{}/",
+                game_version
+            )))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let data: Vec<FabricLoaderManifest> = response.json().await?;
+        Ok(data)
+    }
+
+    /// The loaders of `get_list_fabric_loader_versions` marked as stable.
+    pub async fn get_stable_fabric_loader_versions(
+        &self,
+        game_version: &str,
+    ) -> Result<Vec<FabricLoaderManifest>, ClientDownloaderError> {
+        let mut loaders = self.get_list_fabric_loader_versions(game_version).await?;
+        loaders.retain(|loader| loader.loader.stable);
+        Ok(loaders)
+    }
+
+    /// The stable loader with the highest version, `NoSuchVersion` if there
+    /// is none for `game_version`.
+    pub async fn latest_stable_fabric_loader(
+        &self,
+        game_version: &str,
+    ) -> Result<FabricLoaderManifest, ClientDownloaderError> {
+        self.get_stable_fabric_loader_versions(game_version)
+            .await?
+            .into_iter()
+            .max_by_key(|loader| loader_version_key(&loader.loader.version))
+            .ok_or(ClientDownloaderError::NoSuchVersion)
+    }
+
+    /// Fetches and parses the asset index at `url`, usually the one of
+    /// `manifest.asset_index`.
+    pub async fn fetch_asset_index(&self, url: &str) -> Result<AssetIndex, ClientDownloaderError> {
+        let response = self
+            .client
+            .get(self.options.mirror.rewrite(url))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json().await?)
+    }
+
+    pub fn get_version(&self, id: &str) -> Option<&LauncherManifestVersion> {
+        self.main_manifest
+            .versions
+            .iter()
+            .find(|v| v.id.eq_ignore_ascii_case(id))
+    }
+
+    /// Fetches the full manifest of a version, e.g. to look at its libraries
+    /// or Java version before downloading it.
+    pub async fn get_manifest(&self, version_id: &str) -> Result<Manifest, ClientDownloaderError> {
+        let version = self
+            .get_version(version_id)
+            .ok_or(ClientDownloaderError::NoSuchVersion)?;
+        let cache_path = self.options.manifest_cache_path(version);
+
+        if let Some(cache_path) = &cache_path {
+            if let Ok(bytes) = tokio::fs::read(cache_path).await {
+                if cached_manifest_up_to_date(cache_path, version, &bytes) {
+                    if let Ok(manifest) = serde_json::from_slice(&bytes) {
+                        return Ok(manifest);
+                    }
+                }
+            }
+        }
+
+        let bytes = self
+            .client
+            .get(self.options.mirror.rewrite(&version.url))
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        check_manifest_checksum(version, &bytes)?;
+        let manifest = serde_json::from_slice(&bytes)?;
+
+        if let Some(cache_path) = cache_path {
+            tokio::fs::create_dir_all(parent_dir(&cache_path)?).await?;
+            tokio::fs::write(cache_path, &bytes).await?;
+        }
+
+        Ok(manifest)
+    }
+
+    /// The latest release, `None` if it isn't in the version list.
+    pub fn latest_release(&self) -> Option<&LauncherManifestVersion> {
+        self.get_version(&self.main_manifest.latest.release)
+    }
+
+    /// The latest snapshot, `None` if it isn't in the version list.
+    pub fn latest_snapshot(&self) -> Option<&LauncherManifestVersion> {
+        self.get_version(&self.main_manifest.latest.snapshot)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn download_version(
+        &self,
+        version_id: &str,
+        game_path: &PathBuf,
+        base_path: &PathBuf,
+        manifest_path: Option<&PathBuf>,
+        version_path: Option<&PathBuf>,
+        launcher: Option<Launcher>,
+        launcher_id: Option<&str>,
+        progress: Option<Progress>,
+    ) -> Result<InstalledVersion, ClientDownloaderError> {
+        let manifest_path = manifest_path
+            .unwrap_or(&game_path.join("manifest.json"))
+            .clone();
+
+        let version = self
+            .get_version(version_id)
+            .ok_or(ClientDownloaderError::NoSuchVersion)?;
+        let launcher = launcher.unwrap_or(Launcher::Vanilla);
+        let launcher_id = match (launcher, launcher_id) {
+            (Launcher::Vanilla, _) => "",
+            (_, Some(launcher_id)) => launcher_id,
+            (launcher, None) => return Err(ClientDownloaderError::MissingLauncherId(launcher)),
+        };
+        report(
+            &progress,
+            ProgressEvent::Resolving {
+                metadata: Metadata::VersionManifest,
+            },
+        );
+        let mut manifest = self.get_manifest(&version.id).await?;
+        report(
+            &progress,
+            ProgressEvent::Resolved {
+                metadata: Metadata::VersionManifest,
+            },
+        );
+
+        if launcher != Launcher::Vanilla {
+            report(
+                &progress,
+                ProgressEvent::Resolving {
+                    metadata: Metadata::LoaderManifest,
+                },
+            );
+        }
+
+        let mut forge_installer = None;
+        match launcher {
+            Launcher::Fabric => {
+                manifest = self
+                    .setup_fabric(version_id, launcher_id, &mut manifest)
+                    .await?;
+            }
+            Launcher::Quilt => {
+                manifest = self
+                    .setup_quilt(version_id, launcher_id, &mut manifest)
+                    .await?;
+            }
+            Launcher::Forge => {
+                let (forge, installer) = self
+                    .setup_forge(version_id, launcher_id, &mut manifest, base_path)
+                    .await?;
+                manifest = forge;
+                forge_installer = Some(installer);
+            }
+            Launcher::NeoForge => {
+                let (neoforge, installer) = self
+                    .setup_neoforge(version_id, launcher_id, &mut manifest, base_path)
+                    .await?;
+                manifest = neoforge;
+                forge_installer = Some(installer);
+            }
+            Launcher::Vanilla => {}
+        }
+
+        if launcher != Launcher::Vanilla {
+            report(
+                &progress,
+                ProgressEvent::Resolved {
+                    metadata: Metadata::LoaderManifest,
+                },
+            );
+        }
+
+        let manifest_json = serde_json::to_string_pretty(&manifest)?;
+        tokio::fs::create_dir_all(game_path).await?;
+        tokio::fs::create_dir_all(parent_dir(&manifest_path)?).await?;
+        tokio::fs::write(manifest_path, manifest_json).await?;
+
+        if self.profiles_json {
+            add_profile(game_path, &manifest.id).await?;
+        }
+
+        let results = self
+            .download_files(
+                &manifest,
+                forge_installer.as_ref(),
+                game_path,
+                base_path,
+                version_path,
+                progress,
+            )
+            .await?;
+
+        Ok(InstalledVersion {
+            manifest,
+            results,
+            forge_installer,
+        })
+    }
+
+    pub async fn setup_fabric(
+        &self,
+        version_id: &str,
+        launcher_id: &str,
+        base_manifest: &mut Manifest,
+    ) -> Result<Manifest, ClientDownloaderError> {
+        let response = self
+            .client
+            .get(self.options.mirror.rewrite(&format!(
+                "https://meta.fabricmc.net/v2/versions/loader/{version_id}/{launcher_id}/profile/json"
+            )))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let data: FabricManifest = response.json().await?;
+        Ok(manifest_from_fabric(data, base_manifest)?)
+    }
+
+    pub async fn setup_quilt(
+        &self,
+        version_id: &str,
+        launcher_id: &str,
+        base_manifest: &mut Manifest,
+    ) -> Result<Manifest, ClientDownloaderError> {
+        let response = self
+            .client
+            .get(self.options.mirror.rewrite(&format!(
+                "https://meta.quiltmc.org/v3/versions/loader/{version_id}/{launcher_id}/profile/json"
+            )))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let data: FabricManifest = response.json().await?;
+        Ok(manifest_from_fabric(data, base_manifest)?)
+    }
+
+    /// Fetches the installer jar `coordinate` from the maven `repository`
+    /// and keeps it with the libraries, where `run_processors` reads it.
+    async fn get_installer(
+        &self,
+        repository: &str,
+        coordinate: &str,
+        base_path: &Path,
+    ) -> Result<PathBuf, ClientDownloaderError> {
+        let path = maven_to_path(coordinate)?;
+        let response = self
+            .client
+            .get(self.options.mirror.rewrite(&format!("{repository}{path}")))
+            .send()
+            .await?
+            .error_for_status()?;
+        let bytes = response.bytes().await?.to_vec();
+
+        let installer_path = self.options.paths().library(base_path, &path);
+        tokio::fs::create_dir_all(parent_dir(&installer_path)?).await?;
+        tokio::fs::write(&installer_path, &bytes).await?;
+        Ok(installer_path)
+    }
+
+    /// Also saves the installer jar with the libraries of `base_path`.
+    pub async fn setup_forge(
+        &self,
+        version_id: &str,
+        launcher_id: &str,
+        base_manifest: &mut Manifest,
+        base_path: &PathBuf,
+    ) -> Result<(Manifest, ForgeInstaller), ClientDownloaderError> {
+        let path = self
+            .get_installer(
+                "https://maven.minecraftforge.net/",
+                &format!("net.minecraftforge:forge:{version_id}-{launcher_id}:installer"),
+                base_path,
+            )
+            .await?;
+        let (data, profile) = read_installer(&path)?;
+
+        let manifest = manifest_from_forge(data, base_manifest)?;
+        let libraries = processor_libraries(&profile, &manifest)?;
+        Ok((
+            manifest,
+            ForgeInstaller {
+                path,
+                profile,
+                libraries,
+            },
+        ))
+    }
+
+    /// Also saves the installer jar with the libraries of `base_path`.
+    pub async fn setup_neoforge(
+        &self,
+        _version_id: &str,
+        launcher_id: &str,
+        base_manifest: &mut Manifest,
+        base_path: &PathBuf,
+    ) -> Result<(Manifest, ForgeInstaller), ClientDownloaderError> {
+        let path = self
+            .get_installer(
+                "https://maven.neoforged.net/releases/",
+                &format!("net.neoforged:neoforge:{launcher_id}:installer"),
+                base_path,
+            )
+            .await?;
+        let (data, profile) = read_installer(&path)?;
+
+        let (manifest, libraries) = manifest_from_neoforge(data, &profile, base_manifest)?;
+        Ok((
+            manifest,
+            ForgeInstaller {
+                path,
+                profile,
+                libraries,
+            },
+        ))
+    }
+
+    /// Downloads the dedicated server jar of the version to
+    /// `base_path/server/{id}/server.jar`.
+    pub async fn download_server(
+        &self,
+        manifest: &Manifest,
+        base_path: &PathBuf,
+        progress: Option<Progress>,
+    ) -> Result<DownloadOutput, ClientDownloaderError> {
+        let mut download = server_download(manifest);
+        download.url = self.options.mirror.rewrite(&download.url);
+
+        let results = self
+            .options
+            .downloader_service(base_path.clone())
+            .with_downloads(vec![download])
+            .run_async(progress)
+            .await?;
+
+        match results.into_iter().next() {
+            Some(result) => Ok(result?),
+            None => Err(ClientDownloaderError::UnknownError),
+        }
+    }
+
+    pub async fn download_by_manifest(
+        &self,
+        manifest: &Manifest,
+        game_path: &PathBuf,
+        base_path: &PathBuf,
+        version_path: Option<&PathBuf>,
+        progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+        self.download_files(manifest, None, game_path, base_path, version_path, progress)
+            .await
+    }
+
+    /// `download_by_manifest`, with the processor libraries of the
+    /// installer in the same download.
+    async fn download_files(
+        &self,
+        manifest: &Manifest,
+        forge_installer: Option<&ForgeInstaller>,
+        game_path: &PathBuf,
+        base_path: &PathBuf,
+        version_path: Option<&PathBuf>,
+        progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+        let version_path =
+            version_jar_path(manifest, base_path, version_path, &self.options.paths());
+        tokio::fs::create_dir_all(parent_dir(&version_path)?).await?;
+        tokio::fs::write(
+            version_path.with_extension("json"),
+            serde_json::to_string_pretty(manifest)?,
+        )
+        .await?;
+
+        report(
+            &progress,
+            ProgressEvent::Resolving {
+                metadata: Metadata::AssetIndex,
+            },
+        );
+        let asset_index = self.fetch_asset_index(&manifest.asset_index.url).await?;
+        report(
+            &progress,
+            ProgressEvent::Resolved {
+                metadata: Metadata::AssetIndex,
+            },
+        );
+
+        let mut downloads =
+            self.options
+                .downloads(manifest, base_path, &version_path, &asset_index);
+        if let Some(installer) = forge_installer {
+            downloads.extend(self.options.installer_downloads(installer, base_path));
+        }
+
+        let results = self
+            .options
+            .downloader_service(parent_dir(base_path)?.to_path_buf())
+            .with_downloads(downloads)
+            .run_async(progress)
+            .await?;
+
+        copy_virtual_assets(
+            manifest,
+            base_path,
+            game_path,
+            &asset_index,
+            &self.options.paths(),
+        )?;
+
+        let results = check_results(results)?;
+        self.options
+            .extract_natives(manifest, base_path, &version_path)?;
+        Ok(results)
+    }
+}
+
+/// Adds a profile for `version_id` to the `launcher_profiles.json` of
+/// `game_path`, like `ProfileJson::load` and `save` without blocking.
+async fn add_profile(game_path: &PathBuf, version_id: &str) -> Result<(), ClientDownloaderError> {
+    let path = game_path.join("launcher_profiles.json");
+    let mut profile_json = match tokio::fs::read(&path).await {
+        Ok(bytes) => serde_json::from_slice(&bytes)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => ProfileJson::default(),
+        Err(e) => return Err(e.into()),
+    };
+    profile_json.add_version(version_id);
+    tokio::fs::write(path, serde_json::to_string_pretty(&profile_json)?).await?;
+    Ok(())
+}
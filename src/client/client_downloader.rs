@@ -1,21 +1,64 @@
-use crate::error::{ClientDownloaderError, DownloadError};
+use crate::error::{ClientDownloaderError, ManifestError};
 use crate::json_profiles::ProfileJson;
-use crate::launcher_manifest::{FabricLoaderManifest, LauncherManifest, LauncherManifestVersion};
-use crate::manifest::Manifest;
-use crate::prelude::{manifest_from_fabric, FabricManifest};
-use reqwest::blocking::Client;
-use serde_json::Value;
+use crate::launcher_manifest::{
+    FabricLoaderManifest, LauncherManifest, LauncherManifestVersion, QuiltLoaderManifest,
+};
+use crate::manifest::{
+    manifest_from_inheriting, maven_to_path, native_library_file, processor_libraries,
+    should_download_library, AssetIndex, InheritingManifest, Manifest, ManifestFile,
+    ManifestLibrary, VersionType,
+};
+use crate::prelude::{
+    manifest_from_fabric, manifest_from_forge, manifest_from_neoforge, FabricManifest,
+    ForgeInstallProfile, ForgeManifest,
+};
+use reqwest::blocking::{Client, ClientBuilder};
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
 
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
 
+use super::downloader::report;
+use super::java::{extract_java_archive, java_archive, java_archive_extension, java_executable};
+use super::paths::PathOverrides;
 use super::{
-    DownloadData, DownloadJava, DownloadResult, DownloadVersion, DownloaderService, Progress,
+    extract_natives, verify_bytes, DownloadData, DownloadJava, DownloadOutput, DownloadResult,
+    DownloadVersion, DownloaderService, ForgeInstaller, InstalledVersion, JavaVendor, Metadata,
+    MirrorConfig, PathStrategy, Progress, ProgressEvent, VanillaPaths, VerifyStatus,
 };
 
+/// The official version manifest.
+pub(super) const VERSION_MANIFEST_URL: &str =
+    "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
+
 pub struct ClientDownloader {
     pub main_manifest: LauncherManifest,
+    client: Client,
+    /// The client was given to `with_client` and is never replaced.
+    custom_client: bool,
+    java: Option<(String, JavaVendor)>,
+    pub(super) options: DownloadOptions,
+    profiles_json: bool,
+    main_manifest_url: String,
 }
 
+impl std::fmt::Debug for ClientDownloader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientDownloader")
+            .field("main_manifest", &self.main_manifest)
+            .field("main_manifest_url", &self.main_manifest_url)
+            .field("client", &self.client)
+            .field("java", &self.java)
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Launcher {
     Vanilla,
     Fabric,
@@ -26,47 +69,708 @@ pub enum Launcher {
 
 impl ClientDownloader {
     pub fn new() -> Result<Self, ClientDownloaderError> {
+        let client = default_client()?;
+        Ok(Self::build(Self::init_with_client(&client)?, client))
+    }
+
+    /// Same as `new`, but every metadata request goes through `client`, e.g.
+    /// to set a timeout, a proxy or another `User-Agent`.
+    pub fn with_client(client: Client) -> Result<Self, ClientDownloaderError> {
+        Ok(Self {
+            custom_client: true,
+            ..Self::build(Self::init_with_client(&client)?, client)
+        })
+    }
+
+    /// Same as `new`, but the version manifest is fetched from `url`, e.g. a
+    /// mirror or a local fixture server.
+    pub fn new_from_url(url: &str) -> Result<Self, ClientDownloaderError> {
+        let client = default_client()?;
         Ok(Self {
-            main_manifest: Self::init()?,
+            main_manifest_url: url.to_string(),
+            ..Self::build(Self::fetch_main_manifest(&client, url)?, client)
         })
     }
 
+    /// Builds the downloader from an already fetched or parsed version
+    /// manifest, without any network access.
+    pub fn from_manifest(main_manifest: LauncherManifest) -> Result<Self, ClientDownloaderError> {
+        Ok(Self::build(main_manifest, default_client()?))
+    }
+
+    fn build(main_manifest: LauncherManifest, client: Client) -> Self {
+        Self {
+            main_manifest,
+            client,
+            custom_client: false,
+            java: None,
+            options: DownloadOptions::default(),
+            profiles_json: false,
+            main_manifest_url: VERSION_MANIFEST_URL.to_string(),
+        }
+    }
+
+    /// Builds the downloader from a version manifest saved by
+    /// `init_with_cache`, without any network access.
+    pub fn from_cached(path: &PathBuf) -> Result<Self, ClientDownloaderError> {
+        let Ok(file) = std::fs::File::open(path) else {
+            return Err(ClientDownloaderError::NoSuchCache(path.clone()));
+        };
+        let main_manifest = serde_json::from_reader(std::io::BufReader::new(file))
+            .map_err(|e| ClientDownloaderError::InvalidCache(path.clone(), e))?;
+
+        Self::from_manifest(main_manifest)
+    }
+
+    /// Same as `new`, but every request, including the file downloads, goes
+    /// through the proxy at `url`.
+    pub fn with_proxy(url: &str) -> Result<Self, ClientDownloaderError> {
+        let proxy = reqwest::Proxy::all(url)?;
+        let client = client_builder().proxy(proxy.clone()).build()?;
+
+        let mut downloader = Self::build(Self::init_with_client(&client)?, client);
+        downloader.options.proxy = Some(proxy);
+        Ok(downloader)
+    }
+
+    /// Makes `download_version` also install the Java version the game
+    /// needs into `root_path`.
+    pub fn with_java(&mut self, root_path: &str, vendor: Option<JavaVendor>) -> &mut Self {
+        self.java = Some((root_path.to_string(), vendor.unwrap_or_default()));
+        self
+    }
+
+    /// Lets the file downloads be cancelled by setting `cancel`, see
+    /// `DownloaderService::with_cancel`.
+    pub fn with_cancel(&mut self, cancel: Arc<AtomicBool>) -> &mut Self {
+        self.options.cancel = Some(cancel);
+        self
+    }
+
+    /// Makes `download_by_manifest` also download the official client and
+    /// server mappings, for the versions that publish them.
+    pub fn with_mappings(&mut self, include_mappings: bool) -> &mut Self {
+        self.options.mappings = include_mappings;
+        self
+    }
+
+    /// Downloads the versions, assets, libraries and Fabric metadata from
+    /// `mirror` instead of the official hosts. The version list itself comes
+    /// from wherever it was fetched.
+    pub fn with_mirror(&mut self, mirror: MirrorConfig) -> &mut Self {
+        self.options.mirror = mirror;
+        self
+    }
+
+    /// Stores the client jar, assets and libraries where `paths` says
+    /// instead of the vanilla layout.
+    pub fn with_path_strategy(&mut self, paths: impl PathStrategy + 'static) -> &mut Self {
+        self.options.paths = Arc::new(paths);
+        self
+    }
+
+    /// Stores the asset index, the objects and the logging configurations in
+    /// `assets_path` instead of `base_path/assets`, e.g. to share them
+    /// between instances. Launch with it as the `assets_root`.
+    pub fn with_assets_path(&mut self, assets_path: PathBuf) -> &mut Self {
+        self.options.assets_path = Some(assets_path);
+        self
+    }
+
+    /// Stores the libraries in `libraries_path` instead of
+    /// `base_path/libraries`, e.g. to share them between instances.
+    /// `resolved_libraries` lists them there for the classpath, launch with
+    /// it as the `library_directory`.
+    pub fn with_libraries_path(&mut self, libraries_path: PathBuf) -> &mut Self {
+        self.options.libraries_path = Some(libraries_path);
+        self
+    }
+
+    /// Keeps the version manifests fetched by `get_manifest` in
+    /// `cache_dir`, and reads them from there while they are up to date.
+    ///
+    /// The version list of `refresh_versions` and the Fabric loader lists
+    /// are kept there too, and only downloaded again when they changed.
+    pub fn with_manifest_cache(&mut self, cache_dir: PathBuf) -> &mut Self {
+        self.options.manifest_cache = Some(cache_dir);
+        self
+    }
+
+    /// Skips the libraries matching one of `names` in `download_by_manifest`.
+    ///
+    /// A `group:artifact` name excludes every version of the artifact, a
+    /// full `group:artifact:version` only that one.
+    pub fn with_excluded_libraries(&mut self, names: Vec<String>) -> &mut Self {
+        self.options.excluded_libraries = names;
+        self
+    }
+
+    /// Makes `download_version` add a profile for the version to the
+    /// `launcher_profiles.json` of the game directory.
+    pub fn with_profiles_json(&mut self, profiles_json: bool) -> &mut Self {
+        self.profiles_json = profiles_json;
+        self
+    }
+
+    /// Accepts any TLS certificate, even self-signed or expired ones, for
+    /// the metadata and the file downloads. Off by default.
+    ///
+    /// DANGEROUS: anyone on the network path can then serve the game files.
+    /// Only meant for mirrors on a trusted network. A client given to
+    /// `with_client` is kept as is, only the file downloads accept them then.
+    pub fn with_danger_accept_invalid_certs(
+        &mut self,
+        accept: bool,
+    ) -> Result<&mut Self, ClientDownloaderError> {
+        if !self.custom_client {
+            let mut builder = client_builder().danger_accept_invalid_certs(accept);
+            if let Some(proxy) = &self.options.proxy {
+                builder = builder.proxy(proxy.clone());
+            }
+            self.client = builder.build()?;
+        }
+        self.options.accept_invalid_certs = accept;
+        Ok(self)
+    }
+
+    /// The libraries the manifest uses on this platform, by name, with the
+    /// path `download_by_manifest` stores them at in `base_path`.
+    pub fn resolved_libraries(
+        &self,
+        manifest: &Manifest,
+        base_path: &PathBuf,
+    ) -> Vec<(String, PathBuf)> {
+        library_files(&manifest.libraries)
+            .into_iter()
+            .filter_map(|(name, file)| {
+                let path = match file.path {
+                    Some(path) => path,
+                    None => maven_to_path(name).ok()?,
+                };
+                Some((
+                    name.to_string(),
+                    self.options.paths().library(base_path, &path),
+                ))
+            })
+            .collect()
+    }
+
+    /// The major Java version the manifest asks for.
+    pub fn required_java_version(manifest: &Manifest) -> u8 {
+        // A bogus negative major falls back to Java 8, like a manifest without one
+        u8::try_from(manifest.java_version.major_version).unwrap_or(8)
+    }
+
     pub fn init() -> Result<LauncherManifest, ClientDownloaderError> {
-        let client = Client::new();
-        let response = client
-            .get("https://launchermeta.mojang.com/mc/game/version_manifest.json")
-            .send()?;
+        Self::init_with_client(&default_client()?)
+    }
+
+    /// Same as `init`, also saving the fetched manifest to `cache_path` for
+    /// `from_cached`.
+    pub fn init_with_cache(
+        cache_path: Option<&PathBuf>,
+    ) -> Result<LauncherManifest, ClientDownloaderError> {
+        let manifest = Self::init()?;
+
+        if let Some(cache_path) = cache_path {
+            if let Some(parent) = cache_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(cache_path, serde_json::to_string(&manifest)?)?;
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn init_with_client(client: &Client) -> Result<LauncherManifest, ClientDownloaderError> {
+        Self::fetch_main_manifest(client, VERSION_MANIFEST_URL)
+    }
+
+    /// Same as `init`, but the version manifest is fetched from `url`.
+    pub fn init_from_url(url: &str) -> Result<LauncherManifest, ClientDownloaderError> {
+        Self::fetch_main_manifest(&default_client()?, url)
+    }
+
+    fn fetch_main_manifest(
+        client: &Client,
+        url: &str,
+    ) -> Result<LauncherManifest, ClientDownloaderError> {
+        let response = client.get(url).send()?.error_for_status()?;
 
         let data: LauncherManifest = serde_json::from_reader(response)?;
         Ok(data)
     }
 
+    /// Fetches the version list again, e.g. to see new snapshots.
+    pub fn refresh_versions(&mut self) -> Result<&LauncherManifest, ClientDownloaderError> {
+        let url = self.main_manifest_url.clone();
+        let bytes = self.get_conditional(&url, "version_manifest_v2.json")?;
+        self.main_manifest = serde_json::from_slice(&bytes)?;
+        Ok(&self.main_manifest)
+    }
+
+    /// GETs `url`, stored as `name` in the manifest cache. When it is there,
+    /// the server is asked to only send it again if it changed.
+    fn get_conditional(&self, url: &str, name: &str) -> Result<Vec<u8>, ClientDownloaderError> {
+        let Some(cache_dir) = &self.options.manifest_cache else {
+            return Ok(self
+                .client
+                .get(url)
+                .send()?
+                .error_for_status()?
+                .bytes()?
+                .to_vec());
+        };
+        let body_path = cache_dir.join(name);
+        let validators_path = cache_dir.join(format!("{name}.validators.json"));
+
+        let cached = std::fs::read(&body_path).ok();
+        let validators: CacheValidators = cached
+            .as_ref()
+            .and_then(|_| std::fs::read(&validators_path).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+
+        let mut request = self.client.get(url);
+        if let Some(etag) = &validators.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+        let response = request.send()?.error_for_status()?;
+
+        if let (reqwest::StatusCode::NOT_MODIFIED, Some(cached)) = (response.status(), cached) {
+            return Ok(cached);
+        }
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let validators = CacheValidators {
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        };
+        let bytes = response.bytes()?.to_vec();
+
+        std::fs::create_dir_all(cache_dir)?;
+        std::fs::write(&body_path, &bytes)?;
+        std::fs::write(&validators_path, serde_json::to_vec(&validators)?)?;
+
+        Ok(bytes)
+    }
+
     pub fn get_list_versions(&self) -> Vec<LauncherManifestVersion> {
         self.main_manifest.versions.clone()
     }
 
+    /// The versions sorted by release time. The times are ISO-8601 in UTC,
+    /// so they sort as strings.
+    pub fn get_list_versions_sorted(&self, newest_first: bool) -> Vec<LauncherManifestVersion> {
+        let mut versions = self.main_manifest.versions.clone();
+        versions.sort_by(|a, b| a.release_time.cmp(&b.release_time));
+        if newest_first {
+            versions.reverse();
+        }
+        versions
+    }
+
+    /// The `n` most recently released versions, newest first.
+    pub fn recent_versions(&self, n: usize) -> Vec<LauncherManifestVersion> {
+        let mut versions = self.get_list_versions_sorted(true);
+        versions.truncate(n);
+        versions
+    }
+
+    /// The versions whose type is one of `types`, e.g. only the releases.
+    pub fn get_list_versions_filtered(
+        &self,
+        types: &[VersionType],
+    ) -> Vec<LauncherManifestVersion> {
+        self.main_manifest
+            .versions
+            .iter()
+            .filter(|v| types.contains(&v.version_type))
+            .cloned()
+            .collect()
+    }
+
     pub fn get_list_fabric_loader_versions(
         &self,
         game_version: &str,
     ) -> Result<Vec<FabricLoaderManifest>, ClientDownloaderError> {
-        let client = Client::new();
-        let response = client
-            .get(format!(
+        let bytes = self.get_conditional(
+            &self.options.mirror.rewrite(&format!(
                 "https://meta.fabricmc.net/v2/versions/loader/{}/",
                 game_version
-            ))
-            .send()?;
+            )),
+            &format!("fabric-loader-{game_version}.json"),
+        )?;
+
+        let data: Vec<FabricLoaderManifest> = serde_json::from_slice(&bytes)?;
+        Ok(data)
+    }
+
+    /// The loaders of `get_list_fabric_loader_versions` marked as stable.
+    pub fn get_stable_fabric_loader_versions(
+        &self,
+        game_version: &str,
+    ) -> Result<Vec<FabricLoaderManifest>, ClientDownloaderError> {
+        let mut loaders = self.get_list_fabric_loader_versions(game_version)?;
+        loaders.retain(|loader| loader.loader.stable);
+        Ok(loaders)
+    }
+
+    /// The stable loader with the highest version, `NoSuchVersion` if there
+    /// is none for `game_version`.
+    pub fn latest_stable_fabric_loader(
+        &self,
+        game_version: &str,
+    ) -> Result<FabricLoaderManifest, ClientDownloaderError> {
+        self.get_stable_fabric_loader_versions(game_version)?
+            .into_iter()
+            .max_by_key(|loader| loader_version_key(&loader.loader.version))
+            .ok_or(ClientDownloaderError::NoSuchVersion)
+    }
+
+    pub fn get_list_quilt_loader_versions(
+        &self,
+        game_version: &str,
+    ) -> Result<Vec<QuiltLoaderManifest>, ClientDownloaderError> {
+        let response = self
+            .client
+            .get(self.options.mirror.rewrite(&format!(
+                "https://meta.quiltmc.org/v3/versions/loader/{}",
+                game_version
+            )))
+            .send()?
+            .error_for_status()?;
 
-        let data: Vec<FabricLoaderManifest> = serde_json::from_reader(response)?;
+        let data: Vec<QuiltLoaderManifest> = serde_json::from_reader(response)?;
         Ok(data)
     }
 
+    /// Fetches the installer jar `coordinate` from the maven `repository`
+    /// and keeps it with the libraries, where `run_processors` reads it.
+    fn get_installer(
+        &self,
+        repository: &str,
+        coordinate: &str,
+        base_path: &Path,
+    ) -> Result<PathBuf, ClientDownloaderError> {
+        let path = maven_to_path(coordinate)?;
+        let response = self
+            .client
+            .get(self.options.mirror.rewrite(&format!("{repository}{path}")))
+            .send()?
+            .error_for_status()?;
+        let bytes = response.bytes()?.to_vec();
+
+        let installer_path = self.options.paths().library(base_path, &path);
+        std::fs::create_dir_all(parent_dir(&installer_path)?)?;
+        std::fs::write(&installer_path, &bytes)?;
+        Ok(installer_path)
+    }
+
     pub fn get_version(&self, id: &str) -> Option<&LauncherManifestVersion> {
         self.main_manifest
             .versions
             .iter()
             .find(|v| v.id.eq_ignore_ascii_case(id))
     }
+
+    /// Fetches the full manifest of a version, e.g. to look at its libraries
+    /// or Java version before downloading it.
+    pub fn get_manifest(&self, version_id: &str) -> Result<Manifest, ClientDownloaderError> {
+        let version = self
+            .get_version(version_id)
+            .ok_or(ClientDownloaderError::NoSuchVersion)?;
+        let cache_path = self.options.manifest_cache_path(version);
+
+        if let Some(bytes) = cache_path
+            .as_ref()
+            .and_then(|path| read_cached_manifest(path, version))
+        {
+            if let Ok(manifest) = serde_json::from_slice(&bytes) {
+                return Ok(manifest);
+            }
+        }
+
+        let bytes = self
+            .client
+            .get(self.options.mirror.rewrite(&version.url))
+            .send()?
+            .error_for_status()?
+            .bytes()?;
+        check_manifest_checksum(version, &bytes)?;
+        let manifest = serde_json::from_slice(&bytes)?;
+
+        if let Some(cache_path) = cache_path {
+            std::fs::create_dir_all(parent_dir(&cache_path)?)?;
+            std::fs::write(cache_path, &bytes)?;
+        }
+
+        Ok(manifest)
+    }
+
+    /// Reads a version json from disk and resolves its `inheritsFrom`
+    /// chain into a full manifest.
+    ///
+    /// Each parent is read from `versions/{id}/{id}.json` next to the
+    /// version json when it is there, and fetched with `get_manifest`
+    /// otherwise. A json without `inheritsFrom` is read as is.
+    pub fn resolve_version_json(&self, path: &Path) -> Result<Manifest, ClientDownloaderError> {
+        let versions_path = parent_dir(parent_dir(path)?)?;
+        let mut children: Vec<InheritingManifest> = Vec::new();
+        let mut path = path.to_path_buf();
+
+        let base = loop {
+            let json: serde_json::Value = serde_json::from_slice(&std::fs::read(&path)?)?;
+            if json.get("inheritsFrom").is_none() {
+                break serde_json::from_value(json)?;
+            }
+
+            let child: InheritingManifest = serde_json::from_value(json)?;
+            let parent = child.inherits_from.clone();
+            if parent == child.id || children.iter().any(|c| c.id == parent) {
+                return Err(ManifestError::InheritanceCycle(parent).into());
+            }
+            children.push(child);
+
+            path = versions_path.join(&parent).join(format!("{parent}.json"));
+            if !path.is_file() {
+                break self.get_manifest(&parent)?;
+            }
+        };
+
+        Ok(children.into_iter().rev().try_fold(base, |parent, child| {
+            manifest_from_inheriting(child, &parent)
+        })?)
+    }
+
+    /// The latest release, `None` if it isn't in the version list.
+    pub fn latest_release(&self) -> Option<&LauncherManifestVersion> {
+        self.get_version(&self.main_manifest.latest.release)
+    }
+
+    /// The latest snapshot, `None` if it isn't in the version list.
+    pub fn latest_snapshot(&self) -> Option<&LauncherManifestVersion> {
+        self.get_version(&self.main_manifest.latest.snapshot)
+    }
+
+    /// Fetches and parses the asset index at `url`, usually the one of
+    /// `manifest.asset_index`.
+    pub fn fetch_asset_index(&self, url: &str) -> Result<AssetIndex, ClientDownloaderError> {
+        let response = self
+            .client
+            .get(self.options.mirror.rewrite(url))
+            .send()?
+            .error_for_status()?;
+        Ok(serde_json::from_reader(response)?)
+    }
+
+    /// The files `download_by_manifest` would download, without downloading
+    /// them. Only the asset index is fetched to list the assets.
+    pub fn plan_download_by_manifest(
+        &self,
+        manifest: &Manifest,
+        base_path: &PathBuf,
+        version_path: Option<&PathBuf>,
+    ) -> Result<Vec<DownloadData>, ClientDownloaderError> {
+        let version_path =
+            version_jar_path(manifest, base_path, version_path, &self.options.paths());
+        let asset_index = self.fetch_asset_index(&manifest.asset_index.url)?;
+
+        Ok(self
+            .options
+            .downloads(manifest, base_path, &version_path, &asset_index))
+    }
+
+    /// Downloads only the libraries of the manifest for this platform, e.g.
+    /// after changing the loader of an installed version.
+    pub fn download_libraries_only(
+        &self,
+        manifest: &Manifest,
+        base_path: &PathBuf,
+        progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+        let manifest = self.options.without_excluded_libraries(manifest);
+        let downloads = library_downloads(&manifest.libraries, base_path, &self.options.paths());
+
+        let results = self
+            .options
+            .downloader_service(parent_dir(base_path)?.to_path_buf())
+            .with_downloads(self.options.mirrored(downloads))
+            .run(progress)?;
+
+        check_results(results)
+    }
+
+    /// Downloads only the asset index and the assets of the manifest, e.g.
+    /// to repair missing sounds. The legacy virtual assets aren't copied to
+    /// the game directory, `download_by_manifest` does that.
+    pub fn download_assets_only(
+        &self,
+        manifest: &Manifest,
+        base_path: &PathBuf,
+        progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+        report(
+            &progress,
+            ProgressEvent::Resolving {
+                metadata: Metadata::AssetIndex,
+            },
+        );
+        let asset_index = self.fetch_asset_index(&manifest.asset_index.url)?;
+        report(
+            &progress,
+            ProgressEvent::Resolved {
+                metadata: Metadata::AssetIndex,
+            },
+        );
+        let downloads = asset_downloads(manifest, base_path, &asset_index, &self.options.paths());
+
+        let results = self
+            .options
+            .downloader_service(parent_dir(base_path)?.to_path_buf())
+            .with_downloads(self.options.mirrored(downloads))
+            .run(progress)?;
+
+        check_results(results)
+    }
+
+    /// Same as `download_version`, but for a manifest that isn't in the
+    /// version list, e.g. the version json bundled in a modpack. Nothing is
+    /// looked up or fetched for the version itself.
+    pub fn download_version_from_manifest(
+        &self,
+        manifest: Manifest,
+        game_path: &PathBuf,
+        base_path: &PathBuf,
+        manifest_path: Option<&PathBuf>,
+        version_path: Option<&PathBuf>,
+        progress: Option<Progress>,
+    ) -> Result<InstalledVersion, ClientDownloaderError> {
+        let manifest_path = manifest_path
+            .cloned()
+            .unwrap_or_else(|| game_path.join("manifest.json"));
+
+        self.install_manifest(
+            manifest,
+            None,
+            game_path,
+            base_path,
+            &manifest_path,
+            version_path,
+            progress,
+        )
+    }
+
+    /// The part of `download_version` after the manifest is resolved: the
+    /// Java runtime, the manifest and profile files, then the downloads.
+    #[allow(clippy::too_many_arguments)]
+    fn install_manifest(
+        &self,
+        manifest: Manifest,
+        forge_installer: Option<ForgeInstaller>,
+        game_path: &PathBuf,
+        base_path: &PathBuf,
+        manifest_path: &PathBuf,
+        version_path: Option<&PathBuf>,
+        progress: Option<Progress>,
+    ) -> Result<InstalledVersion, ClientDownloaderError> {
+        if let Some((java_path, vendor)) = &self.java {
+            let java_version = Self::required_java_version(&manifest).to_string();
+            self.download_java(java_path, &java_version, Some(*vendor), progress.clone())?;
+        }
+
+        let manifest_json = serde_json::to_string_pretty(&manifest)?;
+        std::fs::create_dir_all(game_path)?;
+        std::fs::create_dir_all(parent_dir(manifest_path)?)?;
+        std::fs::write(manifest_path, manifest_json)?;
+
+        if self.profiles_json {
+            self.create_profiles_json(game_path, &manifest.id)?;
+        }
+        let results = self.download_files(
+            &manifest,
+            forge_installer.as_ref(),
+            game_path,
+            base_path,
+            version_path,
+            progress,
+        )?;
+
+        Ok(InstalledVersion {
+            manifest,
+            results,
+            forge_installer,
+        })
+    }
+
+    /// How many bytes `download_by_manifest` would download at most, see
+    /// `DownloaderService::estimated_total_bytes`.
+    pub fn estimated_total_bytes(
+        &self,
+        manifest: &Manifest,
+        base_path: &PathBuf,
+        version_path: Option<&PathBuf>,
+    ) -> Result<u64, ClientDownloaderError> {
+        let downloads = self.plan_download_by_manifest(manifest, base_path, version_path)?;
+
+        Ok(DownloaderService::new(PathBuf::new())
+            .with_downloads(downloads)
+            .estimated_total_bytes())
+    }
+
+    /// Downloads the dedicated server jar of the version to
+    /// `base_path/server/{id}/server.jar`.
+    pub fn download_server(
+        &self,
+        manifest: &Manifest,
+        base_path: &PathBuf,
+        progress: Option<Progress>,
+    ) -> Result<DownloadOutput, ClientDownloaderError> {
+        let mut download = server_download(manifest);
+        download.url = self.options.mirror.rewrite(&download.url);
+
+        let results = self
+            .options
+            .downloader_service(base_path.clone())
+            .with_downloads(vec![download])
+            .run(progress)?;
+
+        match results.into_iter().next() {
+            Some(result) => Ok(result?),
+            None => Err(ClientDownloaderError::UnknownError),
+        }
+    }
+
+    /// The ids of the versions installed in `base_path/versions`, i.e. the
+    /// `{id}` directories containing a `{id}.jar`.
+    pub fn installed_versions(base_path: &PathBuf) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(base_path.join("versions")) else {
+            return Vec::new();
+        };
+
+        let mut versions: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let id = entry.file_name().to_string_lossy().to_string();
+                entry
+                    .path()
+                    .join(format!("{id}.jar"))
+                    .is_file()
+                    .then_some(id)
+            })
+            .collect();
+        versions.sort();
+        versions
+    }
 }
 
 impl DownloadJava for ClientDownloader {
@@ -74,31 +778,48 @@ impl DownloadJava for ClientDownloader {
         let mut path = PathBuf::from(root_path);
         path.push(expected_version);
 
-        path.exists() && path.is_dir()
+        java_executable(&path).is_file()
     }
 
-    fn download_java(&self, root_path: &str, version: &str, progress: Option<Progress>) {
+    fn download_java(
+        &self,
+        root_path: &str,
+        version: &str,
+        vendor: Option<JavaVendor>,
+        progress: Option<Progress>,
+    ) -> Result<PathBuf, ClientDownloaderError> {
+        let java_home = PathBuf::from(root_path).join(version);
+
         if !self.check_version(root_path, version) {
-            let os = std::env::consts::OS;
-            let arch = std::env::consts::ARCH;
-            let ext = match os {
-                "macos" | "linux" => ".tar.gz",
-                _ => ".zip",
-            };
+            let ext = java_archive_extension();
+            let archive = java_archive(&self.client, vendor.unwrap_or_default(), version)?;
             let downloads = vec![DownloadData {
-                url: format!(
-          "https://download.oracle.com/java/{version}/archive/jdk-{version}_{os}-{arch}_bin{ext}"
-        ),
+                url: archive.url.clone(),
                 file_name: format!("jdk-{version}{ext}"),
                 output_path: format!("jdk-{version}{ext}"),
                 sha1: String::new(),
                 total_size: 0,
             }];
-            DownloaderService::new(PathBuf::from(root_path))
+            let results = self
+                .options
+                .downloader_service(PathBuf::from(root_path))
                 .with_downloads(downloads)
-                .run(progress)
-                .unwrap();
+                .run(progress)?;
+
+            for result in results {
+                let archive_path = result?.file_path;
+                // A bad archive would otherwise be resumed by the next try
+                if let Err(e) = archive.check(&archive_path) {
+                    std::fs::remove_file(&archive_path)?;
+                    return Err(e);
+                }
+
+                extract_java_archive(&archive_path, &java_home)?;
+                std::fs::remove_file(archive_path)?;
+            }
         }
+
+        Ok(java_executable(&java_home))
     }
 }
 
@@ -113,40 +834,84 @@ impl DownloadVersion for ClientDownloader {
         launcher: Option<Launcher>,
         launcher_id: Option<&str>,
         progress: Option<Progress>,
-    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+    ) -> Result<InstalledVersion, ClientDownloaderError> {
         let manifest_path = manifest_path
-            .unwrap_or(&game_path.join("manifest.json"))
-            .clone();
+            .cloned()
+            .unwrap_or_else(|| game_path.join("manifest.json"));
 
-        let client = Client::new();
-        let version_option = self.get_version(version_id);
+        let version = self
+            .get_version(version_id)
+            .ok_or(ClientDownloaderError::NoSuchVersion)?;
+        let launcher = launcher.unwrap_or(Launcher::Vanilla);
+        let launcher_id = match (launcher, launcher_id) {
+            (Launcher::Vanilla, _) => "",
+            (_, Some(launcher_id)) => launcher_id,
+            (launcher, None) => return Err(ClientDownloaderError::MissingLauncherId(launcher)),
+        };
+        report(
+            &progress,
+            ProgressEvent::Resolving {
+                metadata: Metadata::VersionManifest,
+            },
+        );
+        let mut manifest = self.get_manifest(&version.id)?;
+        report(
+            &progress,
+            ProgressEvent::Resolved {
+                metadata: Metadata::VersionManifest,
+            },
+        );
 
-        if version_option.is_none() {
-            return Err(ClientDownloaderError::NoSuchVersion);
+        if launcher != Launcher::Vanilla {
+            report(
+                &progress,
+                ProgressEvent::Resolving {
+                    metadata: Metadata::LoaderManifest,
+                },
+            );
         }
 
-        let version = version_option.unwrap();
-        let response = client.get(&version.url).send()?;
-        let mut manifest: Manifest = response.json()?;
-
-        match launcher.unwrap_or(Launcher::Vanilla) {
+        let mut forge_installer = None;
+        match launcher {
             Launcher::Fabric => {
-                println!("Setuping fabric");
-
-                manifest = self
-                    .setup_fabric(version_id, launcher_id.unwrap(), &mut manifest)
-                    .unwrap();
+                manifest = self.setup_fabric(version_id, launcher_id, &mut manifest)?;
+            }
+            Launcher::Quilt => {
+                manifest = self.setup_quilt(version_id, launcher_id, &mut manifest)?;
+            }
+            Launcher::Forge => {
+                let (forge, installer) =
+                    self.setup_forge(version_id, launcher_id, &mut manifest, base_path)?;
+                manifest = forge;
+                forge_installer = Some(installer);
             }
-            _ => {}
+            Launcher::NeoForge => {
+                let (neoforge, installer) =
+                    self.setup_neoforge(version_id, launcher_id, &mut manifest, base_path)?;
+                manifest = neoforge;
+                forge_installer = Some(installer);
+            }
+            Launcher::Vanilla => {}
         }
 
-        let manifest_json = serde_json::to_string_pretty(&manifest)?;
-        std::fs::create_dir_all(&game_path)?;
-        std::fs::create_dir_all(&manifest_path.parent().unwrap())?;
-        std::fs::write(manifest_path, manifest_json)?;
+        if launcher != Launcher::Vanilla {
+            report(
+                &progress,
+                ProgressEvent::Resolved {
+                    metadata: Metadata::LoaderManifest,
+                },
+            );
+        }
 
-        self.create_profiles_json(game_path).unwrap();
-        self.download_by_manifest(&manifest, game_path, base_path, version_path, progress)
+        self.install_manifest(
+            manifest,
+            forge_installer,
+            game_path,
+            base_path,
+            &manifest_path,
+            version_path,
+            progress,
+        )
     }
 
     fn setup_fabric(
@@ -155,28 +920,102 @@ impl DownloadVersion for ClientDownloader {
         launcher_id: &str,
         base_manifest: &mut Manifest,
     ) -> Result<Manifest, ClientDownloaderError> {
-        let client = Client::new();
-        let response = client
-            .get(format!(
+        let response = self
+            .client
+            .get(self.options.mirror.rewrite(&format!(
                 "https://meta.fabricmc.net/v2/versions/loader/{version_id}/{launcher_id}/profile/json"
-            ))
-            .send()?;
+            )))
+            .send()?
+            .error_for_status()?;
 
         let data: FabricManifest = serde_json::from_reader(response)?;
 
-        let manifest =
-            manifest_from_fabric(data, base_manifest).expect("Failed to setup fabric manifest");
+        let manifest = manifest_from_fabric(data, base_manifest)?;
         Ok(manifest)
     }
 
-    fn create_profiles_json(&self, game_path: &PathBuf) -> Result<(), ClientDownloaderError> {
-        let profile_json = ProfileJson::default();
+    fn setup_quilt(
+        &self,
+        version_id: &str,
+        launcher_id: &str,
+        base_manifest: &mut Manifest,
+    ) -> Result<Manifest, ClientDownloaderError> {
+        let response = self
+            .client
+            .get(self.options.mirror.rewrite(&format!(
+                "https://meta.quiltmc.org/v3/versions/loader/{version_id}/{launcher_id}/profile/json"
+            )))
+            .send()?
+            .error_for_status()?;
 
-        let profile_json = serde_json::to_string_pretty(&profile_json).unwrap();
-        let profile_json_path = game_path.join("launcher_profiles.json");
-        std::fs::write(&profile_json_path, profile_json).unwrap();
+        // Quilt profiles share the Fabric schema, including the intermediary
+        // and hashed mapping libraries which are plain maven coordinates.
+        let data: FabricManifest = serde_json::from_reader(response)?;
+
+        let manifest = manifest_from_fabric(data, base_manifest)?;
+        Ok(manifest)
+    }
+
+    fn setup_forge(
+        &self,
+        version_id: &str,
+        launcher_id: &str,
+        base_manifest: &mut Manifest,
+        base_path: &PathBuf,
+    ) -> Result<(Manifest, ForgeInstaller), ClientDownloaderError> {
+        let path = self.get_installer(
+            "https://maven.minecraftforge.net/",
+            &format!("net.minecraftforge:forge:{version_id}-{launcher_id}:installer"),
+            base_path,
+        )?;
+        let (data, profile) = read_installer(&path)?;
+
+        let manifest = manifest_from_forge(data, base_manifest)?;
+        let libraries = processor_libraries(&profile, &manifest)?;
+        Ok((
+            manifest,
+            ForgeInstaller {
+                path,
+                profile,
+                libraries,
+            },
+        ))
+    }
+
+    fn setup_neoforge(
+        &self,
+        _version_id: &str,
+        launcher_id: &str,
+        base_manifest: &mut Manifest,
+        base_path: &PathBuf,
+    ) -> Result<(Manifest, ForgeInstaller), ClientDownloaderError> {
+        let path = self.get_installer(
+            "https://maven.neoforged.net/releases/",
+            &format!("net.neoforged:neoforge:{launcher_id}:installer"),
+            base_path,
+        )?;
+        let (data, profile) = read_installer(&path)?;
+
+        let (manifest, libraries) = manifest_from_neoforge(data, &profile, base_manifest)?;
+        Ok((
+            manifest,
+            ForgeInstaller {
+                path,
+                profile,
+                libraries,
+            },
+        ))
+    }
 
-        Ok(())
+    fn create_profiles_json(
+        &self,
+        game_path: &PathBuf,
+        version_id: &str,
+    ) -> Result<(), ClientDownloaderError> {
+        let profile_json_path = game_path.join("launcher_profiles.json");
+        let mut profile_json = ProfileJson::load(&profile_json_path)?;
+        profile_json.add_version(version_id);
+        profile_json.save(&profile_json_path)
     }
 
     fn download_by_manifest(
@@ -187,133 +1026,1335 @@ impl DownloadVersion for ClientDownloader {
         version_path: Option<&PathBuf>,
         progress: Option<Progress>,
     ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
-        let version_path = version_path
-            .unwrap_or(
-                &base_bath
-                    .join("versions")
-                    .join(manifest.clone().id)
-                    .join(format!("{}.jar", manifest.id)),
-            )
-            .clone();
+        self.download_files(manifest, None, game_path, base_bath, version_path, progress)
+    }
+}
 
-        std::fs::create_dir_all(&version_path.parent().unwrap())?;
+impl ClientDownloader {
+    /// `download_by_manifest`, with the processor libraries of the
+    /// installer in the same download.
+    fn download_files(
+        &self,
+        manifest: &Manifest,
+        forge_installer: Option<&ForgeInstaller>,
+        game_path: &PathBuf,
+        base_bath: &PathBuf,
+        version_path: Option<&PathBuf>,
+        progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+        let version_path =
+            version_jar_path(manifest, base_bath, version_path, &self.options.paths());
+        std::fs::create_dir_all(parent_dir(&version_path)?)?;
+        write_version_manifest(manifest, &version_path)?;
 
-        let client = Client::new();
-        let mut downloads: Vec<DownloadData> = Vec::new();
+        report(
+            &progress,
+            ProgressEvent::Resolving {
+                metadata: Metadata::AssetIndex,
+            },
+        );
+        let asset_index = self.fetch_asset_index(&manifest.asset_index.url)?;
+        report(
+            &progress,
+            ProgressEvent::Resolved {
+                metadata: Metadata::AssetIndex,
+            },
+        );
 
-        // Add client
-        {
-            downloads.push(DownloadData {
-                url: manifest.clone().downloads.client.url,
-                file_name: version_path
-                    .file_name()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_string(),
-                output_path: version_path.as_path().to_str().unwrap().to_string(),
-                sha1: manifest.clone().downloads.client.sha1,
-                total_size: manifest.downloads.client.size,
-            });
+        let mut downloads =
+            self.options
+                .downloads(manifest, base_bath, &version_path, &asset_index);
+        if let Some(installer) = forge_installer {
+            downloads.extend(self.options.installer_downloads(installer, base_bath));
         }
 
-        // Add asset index
-        {
-            let mut path = base_bath.clone();
-            path.push("assets");
-            path.push("indexes");
-            path.push(format!("{}.json", manifest.asset_index.id));
+        let results = self
+            .options
+            .downloader_service(parent_dir(base_bath)?.to_path_buf())
+            .with_downloads(downloads)
+            .run(progress)?;
 
-            let path = path.to_str().unwrap();
-            let size = manifest.asset_index.size as u64;
+        copy_virtual_assets(
+            manifest,
+            base_bath,
+            game_path,
+            &asset_index,
+            &self.options.paths(),
+        )?;
+
+        let results = check_results(results)?;
+        self.options
+            .extract_natives(manifest, base_bath, &version_path)?;
+        Ok(results)
+    }
+}
+
+/// The settings the blocking and the async downloader share: where the
+/// files go, where they come from and which ones are left out.
+#[derive(Clone)]
+pub(super) struct DownloadOptions {
+    pub(super) proxy: Option<reqwest::Proxy>,
+    pub(super) cancel: Option<Arc<AtomicBool>>,
+    pub(super) mappings: bool,
+    pub(super) mirror: MirrorConfig,
+    pub(super) paths: Arc<dyn PathStrategy>,
+    pub(super) assets_path: Option<PathBuf>,
+    pub(super) libraries_path: Option<PathBuf>,
+    pub(super) manifest_cache: Option<PathBuf>,
+    pub(super) excluded_libraries: Vec<String>,
+    pub(super) accept_invalid_certs: bool,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            cancel: None,
+            mappings: false,
+            mirror: MirrorConfig::default(),
+            paths: Arc::new(VanillaPaths),
+            assets_path: None,
+            libraries_path: None,
+            manifest_cache: None,
+            excluded_libraries: Vec::new(),
+            accept_invalid_certs: false,
+        }
+    }
+}
+
+impl std::fmt::Debug for DownloadOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The path strategy is left out, it has no `Debug`
+        f.debug_struct("DownloadOptions")
+            .field("proxy", &self.proxy)
+            .field("mappings", &self.mappings)
+            .field("mirror", &self.mirror)
+            .field("assets_path", &self.assets_path)
+            .field("libraries_path", &self.libraries_path)
+            .field("manifest_cache", &self.manifest_cache)
+            .field("excluded_libraries", &self.excluded_libraries)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DownloadOptions {
+    pub(super) fn paths(&self) -> PathOverrides<'_> {
+        PathOverrides {
+            paths: self.paths.as_ref(),
+            assets: self.assets_path.as_deref(),
+            libraries: self.libraries_path.as_deref(),
+        }
+    }
+
+    /// Where `get_manifest` caches the manifest of `version`, if it does.
+    pub(super) fn manifest_cache_path(&self, version: &LauncherManifestVersion) -> Option<PathBuf> {
+        self.manifest_cache
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.json", version.id)))
+    }
+
+    /// The files of `download_by_manifest`: without the excluded libraries,
+    /// with the mappings when asked for, from the mirror.
+    pub(super) fn downloads(
+        &self,
+        manifest: &Manifest,
+        base_path: &PathBuf,
+        version_path: &PathBuf,
+        asset_index: &AssetIndex,
+    ) -> Vec<DownloadData> {
+        let manifest = self.without_excluded_libraries(manifest);
+
+        let mut downloads = manifest_downloads(
+            &manifest,
+            base_path,
+            version_path,
+            asset_index,
+            &self.paths(),
+        );
+        if self.mappings {
+            downloads.extend(mappings_downloads(&manifest, version_path));
+        }
+        self.mirrored(downloads)
+    }
+
+    /// Extracts the natives of the manifest into `natives` next to the
+    /// client jar at `version_path`.
+    pub(super) fn extract_natives(
+        &self,
+        manifest: &Manifest,
+        base_path: &Path,
+        version_path: &Path,
+    ) -> Result<Vec<PathBuf>, ClientDownloaderError> {
+        extract_natives(
+            &self.without_excluded_libraries(manifest),
+            base_path,
+            &self.paths(),
+            &parent_dir(version_path)?.join("natives"),
+        )
+    }
+
+    /// The libraries only the processors of the installer use.
+    pub(super) fn installer_downloads(
+        &self,
+        installer: &ForgeInstaller,
+        base_path: &PathBuf,
+    ) -> Vec<DownloadData> {
+        self.mirrored(library_downloads(
+            &installer.libraries,
+            base_path,
+            &self.paths(),
+        ))
+    }
+
+    pub(super) fn without_excluded_libraries(&self, manifest: &Manifest) -> Manifest {
+        let mut manifest = manifest.clone();
+        manifest
+            .libraries
+            .retain(|library| !library_excluded(&library.name, &self.excluded_libraries));
+        manifest
+    }
+
+    pub(super) fn mirrored(&self, mut downloads: Vec<DownloadData>) -> Vec<DownloadData> {
+        for download in &mut downloads {
+            download.url = self.mirror.rewrite(&download.url);
+        }
+        downloads
+    }
+
+    pub(super) fn downloader_service(&self, download_folder: PathBuf) -> DownloaderService {
+        let mut service = DownloaderService::new(download_folder);
+        if let Some(proxy) = &self.proxy {
+            service.with_proxy(proxy.clone());
+        }
+        if let Some(cancel) = &self.cancel {
+            service.with_cancel(cancel.clone());
+        }
+        if self.accept_invalid_certs {
+            service.with_danger_accept_invalid_certs(true);
+        }
+        service
+    }
+}
+
+/// The numbers of a loader version, e.g. `[0, 16, 10]` for `0.16.10`, to
+/// compare versions by. The `build` of the Fabric meta is only the last one.
+pub(super) fn loader_version_key(version: &str) -> Vec<u64> {
+    version
+        .split(['.', '+', '-'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+/// The results of a download, an error only when there were files to
+/// download and every one of them failed.
+pub(super) fn check_results(
+    results: Vec<DownloadResult>,
+) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+    if results.is_empty() || results.iter().any(Result::is_ok) {
+        return Ok(results);
+    }
+
+    Err(ClientDownloaderError::AllDownloadsFailed(
+        results.into_iter().filter_map(Result::err).collect(),
+    ))
+}
+
+/// The directory containing `path`, an error for paths without one.
+pub(super) fn parent_dir(path: &Path) -> Result<&Path, ClientDownloaderError> {
+    path.parent()
+        .ok_or_else(|| ClientDownloaderError::InvalidPath(path.to_path_buf()))
+}
+
+/// Makes sure a fetched version manifest is the one the launcher manifest
+/// points to, when it publishes a hash for it.
+pub(super) fn check_manifest_checksum(
+    version: &LauncherManifestVersion,
+    bytes: &[u8],
+) -> Result<(), ClientDownloaderError> {
+    let Some(sha1) = &version.sha1 else {
+        return Ok(());
+    };
+    if verify_bytes(sha1, bytes) == VerifyStatus::Failed {
+        return Err(ClientDownloaderError::ManifestChecksumMismatch(
+            version.id.clone(),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether the library `name` is one of `excluded`, or a version of one.
+fn library_excluded(name: &str, excluded: &[String]) -> bool {
+    excluded.iter().any(|excluded| {
+        name.strip_prefix(excluded.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(':'))
+    })
+}
+
+/// How long a cached version manifest without a sha1 to check it against is
+/// used.
+const MANIFEST_CACHE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The cached manifest of `version` at `path`, if it is up to date: it
+/// matches the sha1 of the version list or, without one, is recent enough.
+fn read_cached_manifest(path: &Path, version: &LauncherManifestVersion) -> Option<Vec<u8>> {
+    let bytes = std::fs::read(path).ok()?;
+    cached_manifest_up_to_date(path, version, &bytes).then_some(bytes)
+}
+
+/// Whether `bytes`, read from `path`, are an up to date cache of the manifest
+/// of `version`.
+pub(super) fn cached_manifest_up_to_date(
+    path: &Path,
+    version: &LauncherManifestVersion,
+    bytes: &[u8],
+) -> bool {
+    match &version.sha1 {
+        Some(sha1) => verify_bytes(sha1, bytes) == VerifyStatus::Ok,
+        None => path
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age < MANIFEST_CACHE_MAX_AGE),
+    }
+}
+
+/// What a cached response of `get_conditional` is revalidated with.
+#[derive(Default, Serialize, Deserialize)]
+struct CacheValidators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// How the crate identifies itself to the servers.
+pub(super) const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+/// The timeouts of the metadata requests, for both downloaders.
+pub(super) const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub(super) const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The client used when none is given, identifying the crate to the servers.
+fn client_builder() -> ClientBuilder {
+    Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+}
+
+fn default_client() -> Result<Client, ClientDownloaderError> {
+    Ok(client_builder().build()?)
+}
+
+pub(super) fn server_download(manifest: &Manifest) -> DownloadData {
+    DownloadData {
+        output_path: format!("server/{}/server.jar", manifest.id),
+        file_name: "server.jar".to_string(),
+        ..DownloadData::from(manifest.downloads.server.clone())
+    }
+}
+
+/// The official mappings of the version, next to its jar. Older versions
+/// don't have any.
+fn mappings_downloads(manifest: &Manifest, version_path: &Path) -> Vec<DownloadData> {
+    [
+        ("client_mappings.txt", &manifest.downloads.client_mappings),
+        ("server_mappings.txt", &manifest.downloads.server_mappings),
+    ]
+    .into_iter()
+    .filter_map(|(file_name, file)| {
+        Some(DownloadData {
+            file_name: file_name.to_string(),
+            output_path: version_path.with_file_name(file_name).to_str()?.to_string(),
+            ..DownloadData::from(file.clone()?)
+        })
+    })
+    .collect()
+}
+
+/// Saves the manifest as `{id}.json` next to the version jar, the way the
+/// official launcher lays out its versions.
+/// The version json and the install profile of a Forge or NeoForge
+/// installer jar.
+pub(super) fn read_installer(
+    path: &Path,
+) -> Result<(ForgeManifest, ForgeInstallProfile), ClientDownloaderError> {
+    let mut installer = ZipArchive::new(std::fs::File::open(path)?)?;
+    let data = serde_json::from_reader(installer.by_name("version.json")?)?;
+    let profile = serde_json::from_reader(installer.by_name("install_profile.json")?)?;
+    Ok((data, profile))
+}
+
+pub(super) fn write_version_manifest(
+    manifest: &Manifest,
+    version_path: &Path,
+) -> Result<(), ClientDownloaderError> {
+    let manifest_json = serde_json::to_string_pretty(manifest)?;
+    std::fs::write(version_path.with_extension("json"), manifest_json)?;
+    Ok(())
+}
+
+pub(super) fn version_jar_path(
+    manifest: &Manifest,
+    base_path: &PathBuf,
+    version_path: Option<&PathBuf>,
+    paths: &dyn PathStrategy,
+) -> PathBuf {
+    version_path
+        .cloned()
+        .unwrap_or_else(|| paths.client_jar(base_path, &manifest.id))
+}
+
+/// The library jars the manifest uses on this platform, with the name of
+/// their library.
+fn library_files(libraries: &[ManifestLibrary]) -> Vec<(&str, ManifestFile)> {
+    libraries
+        .iter()
+        .filter(|l| should_download_library(l))
+        .flat_map(|l| {
+            let mut files = vec![l.downloads.artifact.clone()];
+            // Older manifests keep the natives in a classifier.
+            if l.natives.is_some() {
+                files.push(native_library_file(l));
+            }
+            files
+                .into_iter()
+                .flatten()
+                .map(|file| (l.name.as_str(), file))
+        })
+        .collect()
+}
+
+/// Lists the client, asset index, assets and libraries of a manifest.
+pub(super) fn manifest_downloads(
+    manifest: &Manifest,
+    base_path: &PathBuf,
+    version_path: &PathBuf,
+    asset_index: &AssetIndex,
+    paths: &dyn PathStrategy,
+) -> Vec<DownloadData> {
+    let mut downloads: Vec<DownloadData> = Vec::new();
+
+    // Add client
+    {
+        downloads.push(DownloadData {
+            url: manifest.clone().downloads.client.url,
+            file_name: version_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            output_path: version_path.to_string_lossy().to_string(),
+            sha1: manifest.clone().downloads.client.sha1,
+            total_size: manifest.downloads.client.size,
+        });
+    }
+
+    // Add logging configuration
+    {
+        let file = &manifest.logging.client.file;
+        if !file.url.is_empty() {
+            let path = paths.assets(base_path).join("log_configs").join(&file.id);
 
             downloads.push(DownloadData {
-                url: manifest.asset_index.url.clone(),
-                file_name: format!("{}.json", manifest.asset_index.id),
-                output_path: path.to_string(),
-                sha1: manifest.clone().asset_index.sha1,
-                total_size: size,
+                url: file.url.clone(),
+                file_name: file.id.clone(),
+                output_path: path.to_string_lossy().to_string(),
+                sha1: file.sha1.clone(),
+                total_size: file.size,
             });
         }
+    }
 
-        // Add assets
-        {
-            let mut path = base_bath.clone();
-            path.push("assets");
-
-            let mut objects_path = path.clone();
-            objects_path.push("objects");
-
-            let response = client.get(manifest.clone().asset_index.url).send()?;
-
-            let data: Value = serde_json::from_reader(response)?;
-            let object = data.get("objects").unwrap().as_object().unwrap();
-            downloads.extend(
-                object
-                    .iter()
-                    .map(|(p, obj)| {
-                        let hash = obj.get("hash").unwrap().as_str().unwrap();
-                        let size = obj.get("size").unwrap().as_u64().unwrap();
-
-                        let mut path = objects_path.clone();
-                        path.push(hash[..2].to_string());
-                        path.push(hash.to_string());
-
-                        DownloadData {
-                            url: format!(
-                                "https://resources.download.minecraft.net/{}/{}",
-                                hash[..2].to_string(),
-                                hash
-                            ),
-                            file_name: p.clone(),
-                            output_path: path.to_str().unwrap().to_string(),
-                            sha1: hash.to_string(),
-                            total_size: size,
-                        }
+    // Add asset index and assets
+    downloads.extend(asset_downloads(manifest, base_path, asset_index, paths));
+
+    // Add libraries to download
+    downloads.extend(library_downloads(&manifest.libraries, base_path, paths));
+
+    downloads
+}
+
+/// The asset index and the objects listed in it, each object once.
+pub(super) fn asset_downloads(
+    manifest: &Manifest,
+    base_path: &PathBuf,
+    asset_index: &AssetIndex,
+    paths: &dyn PathStrategy,
+) -> Vec<DownloadData> {
+    let mut downloads: Vec<DownloadData> = Vec::new();
+
+    // Add asset index
+    {
+        let path = paths
+            .assets(base_path)
+            .join("indexes")
+            .join(format!("{}.json", manifest.asset_index.id));
+
+        let size = manifest.asset_index.size as u64;
+
+        downloads.push(DownloadData {
+            url: manifest.asset_index.url.clone(),
+            file_name: format!("{}.json", manifest.asset_index.id),
+            output_path: path.to_string_lossy().to_string(),
+            sha1: manifest.clone().asset_index.sha1,
+            total_size: size,
+        });
+    }
+
+    // Add assets
+    {
+        // Several logical paths can share the same object, fetch it once
+        let mut hashes = HashSet::new();
+        downloads.extend(
+            asset_index
+                .objects
+                .iter()
+                .filter_map(|(p, obj)| {
+                    let hash = obj.hash.as_str();
+                    if hash.len() < 2 || !hashes.insert(hash) {
+                        return None;
+                    }
+
+                    let path = paths.asset_object(base_path, hash);
+
+                    Some(DownloadData {
+                        url: format!(
+                            "https://resources.download.minecraft.net/{}/{}",
+                            &hash[..2],
+                            hash
+                        ),
+                        file_name: p.clone(),
+                        output_path: path.to_string_lossy().to_string(),
+                        sha1: hash.to_string(),
+                        total_size: obj.size,
                     })
-                    .collect::<Vec<DownloadData>>(),
-            );
+                })
+                .collect::<Vec<DownloadData>>(),
+        );
+    }
+
+    downloads
+}
+
+/// The library jars of the manifest to download for this platform.
+pub(super) fn library_downloads(
+    libraries: &[ManifestLibrary],
+    base_path: &PathBuf,
+    paths: &dyn PathStrategy,
+) -> Vec<DownloadData> {
+    library_files(libraries)
+        .into_iter()
+        .filter_map(|(_, artifact)| {
+            // Artifacts without an url are generated locally by the loader
+            // installers, there is nothing to fetch.
+            if artifact.url.is_empty() {
+                return None;
+            }
+            let path = paths.library(base_path, artifact.path.as_deref().unwrap_or_default());
+            Some(DownloadData {
+                output_path: path.to_string_lossy().to_string(),
+                ..DownloadData::from(artifact)
+            })
+        })
+        .collect()
+}
+
+/// Copies the assets of legacy versions to where they expect them.
+///
+/// Versions before 1.7 read the assets by their logical path, from
+/// `assets/virtual/legacy` and, for `pre-1.6`, from the game `resources`.
+pub(super) fn copy_virtual_assets(
+    manifest: &Manifest,
+    base_path: &PathBuf,
+    game_path: &PathBuf,
+    asset_index: &AssetIndex,
+    paths: &dyn PathStrategy,
+) -> Result<(), ClientDownloaderError> {
+    let mut targets = match manifest.assets.as_str() {
+        "legacy" | "pre-1.6" => vec![paths.assets(base_path).join("virtual").join("legacy")],
+        _ => return Ok(()),
+    };
+    if manifest.assets == "pre-1.6" {
+        targets.push(game_path.join("resources"));
+    }
+
+    for (logical_path, obj) in &asset_index.objects {
+        let hash = obj.hash.as_str();
+        if hash.len() < 2 {
+            continue;
+        }
+        let object_path = paths.asset_object(base_path, hash);
+        if !object_path.is_file() {
+            continue;
         }
 
-        // Add libraries to download
-        {
-            let mut path = base_bath.to_path_buf();
-            path.push("libraries");
-            downloads.extend(
-                manifest
-                    .libraries
-                    .iter()
-                    .filter_map(|l| {
-                        if let Some(artifact) = l.downloads.artifact.clone() {
-                            let mut path = path.clone();
-                            if let Some(p) = artifact.clone().path {
-                                path.push(p);
-                            }
-                            let data = DownloadData {
-                                output_path: path.to_str().unwrap().to_string(),
-                                ..DownloadData::from(artifact)
-                            };
-                            return Some(data);
-                        }
-                        None
-                    })
-                    .collect::<Vec<DownloadData>>(),
-            );
+        for target in &targets {
+            let output_path = target.join(logical_path);
+            if output_path.is_file() {
+                continue;
+            }
+            if let Some(parent) = output_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(&object_path, output_path)?;
         }
+    }
 
-        self.create_profiles_json(game_path).unwrap();
+    Ok(())
+}
 
-        let results = DownloaderService::new(base_bath.parent().unwrap().to_path_buf())
-            .with_downloads(downloads)
-            .run(progress)
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::json;
+
+    use super::{
+        check_results, library_excluded, manifest_downloads, mappings_downloads,
+        read_cached_manifest, server_download, ClientDownloader, Launcher, PathOverrides,
+    };
+    use crate::client::DownloadVersion;
+    use crate::client::{DownloadOutput, VanillaPaths};
+    use crate::error::{ClientDownloaderError, DownloadError, ManifestError};
+    use crate::launcher_manifest::LauncherManifestVersion;
+    use crate::manifest::{AssetIndex, Manifest, VersionType};
+
+    fn test_manifest() -> Manifest {
+        let file = |url: &str| json!({"sha1": "", "size": 1, "url": url});
+        serde_json::from_value(json!({
+            "arguments": {},
+            "assetIndex": {"id": "5", "sha1": "", "size": 1, "totalSize": 1, "url": "https://example.com/5.json"},
+            "assets": "5",
+            "complianceLevel": 1,
+            "downloads": {"client": file("https://example.com/client.jar"), "server": file("https://example.com/server.jar")},
+            "id": "1.20.1",
+            "javaVersion": {"component": "java-runtime-gamma", "majorVersion": 17},
+            "libraries": [
+                {
+                    "downloads": {"artifact": {"path": "a/a.jar", "sha1": "", "size": 1, "url": "https://example.com/a.jar"}},
+                    "name": "a:a:1"
+                },
+                {
+                    "downloads": {"artifact": {"path": "b/b.jar", "sha1": "", "size": 1, "url": "https://example.com/b.jar"}},
+                    "name": "b:b:1",
+                    "rules": [{"action": "allow", "os": {"name": "amiga"}}]
+                }
+            ],
+            "logging": {"client": {"argument": "-Dlog4j.configurationFile=${path}", "file": {"id": "client-1.12.xml", "sha1": "", "size": 1, "url": "https://example.com/client-1.12.xml"}, "type": "log4j2-xml"}},
+            "mainClass": "net.minecraft.client.main.Main",
+            "minimumLauncherVersion": 21,
+            "releaseTime": "2023-06-12T13:25:51+00:00",
+            "time": "2023-06-12T13:25:51+00:00",
+            "type": "release"
+        }))
+        .unwrap()
+    }
+
+    fn test_asset_index() -> AssetIndex {
+        serde_json::from_value(json!({"objects": {
+            "icons/icon_16x16.png": {"hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3665}
+        }}))
+        .unwrap()
+    }
+
+    #[test]
+    fn from_cached_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("version_manifest.json");
+
+        assert!(matches!(
+            ClientDownloader::from_cached(&path),
+            Err(ClientDownloaderError::NoSuchCache(_))
+        ));
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(matches!(
+            ClientDownloader::from_cached(&path),
+            Err(ClientDownloaderError::InvalidCache(_, _))
+        ));
+
+        std::fs::write(
+            &path,
+            r#"{
+                "latest": {"release": "1.20.1", "snapshot": "23w31a"},
+                "versions": [{
+                    "id": "1.20.1",
+                    "releaseTime": "2023-06-12T13:25:51+00:00",
+                    "time": "2023-06-12T13:25:51+00:00",
+                    "type": "release",
+                    "url": "https://piston-meta.mojang.com/v1/packages/1.20.1.json"
+                }, {
+                    "id": "a1.0.4",
+                    "releaseTime": "2010-07-09T22:00:00+00:00",
+                    "time": "2010-07-09T22:00:00+00:00",
+                    "type": "old_alpha",
+                    "url": "https://piston-meta.mojang.com/v1/packages/a1.0.4.json"
+                }]
+            }"#,
+        )
+        .unwrap();
+        let downloader = ClientDownloader::from_cached(&path).unwrap();
+        assert!(downloader.get_version("1.20.1").is_some());
+        assert_eq!(
+            downloader.get_version("a1.0.4").unwrap().version_type,
+            VersionType::OldAlpha
+        );
+        assert_eq!(downloader.latest_release().unwrap().id, "1.20.1");
+        assert!(downloader.latest_snapshot().is_none());
+    }
+
+    #[test]
+    fn versions_are_filtered_without_network() {
+        let main_manifest = serde_json::from_value(json!({
+            "latest": {"release": "1.20.1", "snapshot": "23w31a"},
+            "versions": [
+                {"id": "23w31a", "type": "snapshot", "url": "", "time": "", "releaseTime": ""},
+                {"id": "1.20.1", "type": "release", "url": "", "time": "", "releaseTime": ""}
+            ]
+        }))
+        .unwrap();
+        let downloader = ClientDownloader::from_manifest(main_manifest).unwrap();
+
+        let releases = downloader.get_list_versions_filtered(&[VersionType::Release]);
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].id, "1.20.1");
+        assert_eq!(downloader.latest_snapshot().unwrap().id, "23w31a");
+    }
+
+    #[test]
+    fn versions_are_sorted_by_release_time() {
+        let version = |id: &str, release_time: &str| json!({"id": id, "type": "release", "url": "", "time": "", "releaseTime": release_time});
+        let downloader = ClientDownloader::from_manifest(
+            serde_json::from_value(json!({
+                "latest": {"release": "1.20.1", "snapshot": "1.20.1"},
+                "versions": [
+                    version("1.19.4", "2023-03-14T12:56:18+00:00"),
+                    version("1.20.1", "2023-06-12T13:25:51+00:00"),
+                    version("1.7.10", "2014-05-14T17:29:23+00:00"),
+                ]
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        let ids = |versions: Vec<LauncherManifestVersion>| {
+            versions.into_iter().map(|v| v.id).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ids(downloader.get_list_versions_sorted(false)),
+            ["1.7.10", "1.19.4", "1.20.1"]
+        );
+        assert_eq!(ids(downloader.recent_versions(2)), ["1.20.1", "1.19.4"]);
+    }
+
+    #[test]
+    fn libraries_can_be_shared() {
+        let mut downloader = ClientDownloader::from_manifest(
+            serde_json::from_value(json!({
+                "latest": {"release": "", "snapshot": ""},
+                "versions": []
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        downloader.with_libraries_path(PathBuf::from("/shared/libraries"));
+
+        let libraries =
+            downloader.resolved_libraries(&test_manifest(), &PathBuf::from("/tmp/.minecraft"));
+        let paths: Vec<&PathBuf> = libraries.iter().map(|(_, path)| path).collect();
+        assert_eq!(paths, [&PathBuf::from("/shared/libraries/a/a.jar")]);
+    }
+
+    #[test]
+    fn natives_are_extracted_from_the_libraries_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().join(".minecraft");
+        let shared = dir.path().join("shared");
+
+        let jar_path = shared.join("n/n/1/n-1-natives.jar");
+        std::fs::create_dir_all(jar_path.parent().unwrap()).unwrap();
+        let mut jar = zip::ZipWriter::new(std::fs::File::create(&jar_path).unwrap());
+        for name in ["liblwjgl.so", "META-INF/MANIFEST.MF"] {
+            jar.start_file(name, Default::default()).unwrap();
+            std::io::Write::write_all(&mut jar, b"native").unwrap();
+        }
+        jar.finish().unwrap();
+
+        let mut manifest = test_manifest();
+        manifest.libraries.push(
+            serde_json::from_value(json!({
+                "downloads": {"classifiers": {"natives": {"path": "n/n/1/n-1-natives.jar", "sha1": "", "size": 1, "url": ""}}},
+                "name": "n:n:1",
+                "natives": {crate::manifest::current_os_name(): "natives"}
+            }))
+            .unwrap(),
+        );
+        let mut downloader = ClientDownloader::from_manifest(
+            serde_json::from_value(json!({
+                "latest": {"release": "", "snapshot": ""},
+                "versions": []
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        downloader.with_libraries_path(shared);
+
+        let version_path = base_path.join("versions/1.20.1/1.20.1.jar");
+        let extracted = downloader
+            .options
+            .extract_natives(&manifest, &base_path, &version_path)
             .unwrap();
 
-        if results.is_empty() {
-            return Err(ClientDownloaderError::Download(
-                DownloadError::DownloadDefinition("No Downloaded files".to_string()),
-            ));
+        let natives = base_path.join("versions/1.20.1/natives");
+        assert_eq!(extracted, [natives.join("liblwjgl.so")]);
+        assert!(!natives.join("META-INF").exists());
+    }
+
+    #[test]
+    fn inherited_version_jsons_are_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        let versions = dir.path().join("versions");
+        let write = |id: &str, json: String| {
+            std::fs::create_dir_all(versions.join(id)).unwrap();
+            std::fs::write(versions.join(id).join(format!("{id}.json")), json).unwrap();
+        };
+        write("1.20.1", serde_json::to_string(&test_manifest()).unwrap());
+        write(
+            "fabric-1.20.1",
+            json!({
+                "id": "fabric-1.20.1",
+                "inheritsFrom": "1.20.1",
+                "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient",
+                "libraries": [{"name": "net.fabricmc:fabric-loader:0.14.22", "url": "https://maven.fabricmc.net/"}]
+            })
+            .to_string(),
+        );
+        write(
+            "pack",
+            json!({
+                "id": "pack",
+                "inheritsFrom": "fabric-1.20.1",
+                "arguments": {"jvm": ["-Dpack=true"]}
+            })
+            .to_string(),
+        );
+        let downloader = ClientDownloader::from_manifest(
+            serde_json::from_value(json!({
+                "latest": {"release": "", "snapshot": ""},
+                "versions": []
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let manifest = downloader
+            .resolve_version_json(&versions.join("pack/pack.json"))
+            .unwrap();
+        assert_eq!(manifest.id, "pack");
+        assert_eq!(
+            manifest.main_class,
+            "net.fabricmc.loader.impl.launch.knot.KnotClient"
+        );
+        let names: Vec<&str> = manifest.libraries.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names[0], "net.fabricmc:fabric-loader:0.14.22");
+        assert_eq!(names.len(), test_manifest().libraries.len() + 1);
+        assert_eq!(manifest.arguments.jvm.len(), 1);
+
+        write(
+            "1.20.1",
+            json!({"id": "1.20.1", "inheritsFrom": "pack"}).to_string(),
+        );
+        assert!(matches!(
+            downloader.resolve_version_json(&versions.join("pack/pack.json")),
+            Err(ClientDownloaderError::Manifest(
+                ManifestError::InheritanceCycle(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn manifest_downloads_lists_every_file() {
+        let base_path = PathBuf::from("/tmp/.minecraft");
+        let version_path = base_path.join("versions/1.20.1/1.20.1.jar");
+        let downloads = manifest_downloads(
+            &test_manifest(),
+            &base_path,
+            &version_path,
+            &test_asset_index(),
+            &VanillaPaths,
+        );
+
+        let urls: Vec<&str> = downloads.iter().map(|d| d.url()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://example.com/client.jar",
+                "https://example.com/client-1.12.xml",
+                "https://example.com/5.json",
+                "https://resources.download.minecraft.net/bd/bdf48ef6b5d0d23bbb02e17d04865216179f510a",
+                "https://example.com/a.jar",
+            ]
+        );
+        assert_eq!(
+            PathBuf::from(downloads[1].output_path()),
+            base_path.join("assets/log_configs/client-1.12.xml")
+        );
+        assert_eq!(
+            PathBuf::from(downloads[4].output_path()),
+            base_path.join("libraries/a/a.jar")
+        );
+    }
+
+    #[test]
+    fn assets_can_be_stored_elsewhere() {
+        let base_path = PathBuf::from("/tmp/.minecraft");
+        let assets_path = PathBuf::from("/shared/assets");
+        let paths = PathOverrides {
+            paths: &VanillaPaths,
+            assets: Some(&assets_path),
+            libraries: None,
+        };
+        let downloads = manifest_downloads(
+            &test_manifest(),
+            &base_path,
+            &base_path.join("versions/1.20.1/1.20.1.jar"),
+            &test_asset_index(),
+            &paths,
+        );
+
+        let output_paths: Vec<PathBuf> = downloads
+            .iter()
+            .map(|d| PathBuf::from(d.output_path()))
+            .collect();
+        assert_eq!(
+            output_paths,
+            [
+                base_path.join("versions/1.20.1/1.20.1.jar"),
+                assets_path.join("log_configs/client-1.12.xml"),
+                assets_path.join("indexes/5.json"),
+                assets_path.join("objects/bd/bdf48ef6b5d0d23bbb02e17d04865216179f510a"),
+                base_path.join("libraries/a/a.jar"),
+            ]
+        );
+    }
+
+    #[test]
+    fn server_jar_download() {
+        let download = server_download(&test_manifest());
+
+        assert_eq!(download.url(), "https://example.com/server.jar");
+        assert_eq!(download.output_path(), "server/1.20.1/server.jar");
+    }
+
+    #[test]
+    fn missing_mappings_are_skipped() {
+        let version_path = PathBuf::from("/tmp/.minecraft/versions/custom/custom.jar");
+        let mut manifest = test_manifest();
+        assert!(mappings_downloads(&manifest, &version_path).is_empty());
+
+        manifest.downloads.client_mappings = Some(manifest.downloads.client.clone());
+        let downloads = mappings_downloads(&manifest, &version_path);
+        assert_eq!(downloads.len(), 1);
+        assert_eq!(
+            PathBuf::from(downloads[0].output_path()),
+            PathBuf::from("/tmp/.minecraft/versions/custom/client_mappings.txt")
+        );
+    }
+
+    #[test]
+    fn shared_asset_objects_are_downloaded_once() {
+        let base_path = PathBuf::from("/tmp/.minecraft");
+        let version_path = base_path.join("versions/1.20.1/1.20.1.jar");
+        let asset_index: AssetIndex = serde_json::from_value(json!({"objects": {
+            "a.ogg": {"hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 1},
+            "b.ogg": {"hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 1}
+        }}))
+        .unwrap();
+        let downloads = manifest_downloads(
+            &test_manifest(),
+            &base_path,
+            &version_path,
+            &asset_index,
+            &VanillaPaths,
+        );
+
+        let assets = downloads
+            .iter()
+            .filter(|d| {
+                d.url()
+                    .starts_with("https://resources.download.minecraft.net/")
+            })
+            .count();
+        assert_eq!(assets, 1);
+    }
+
+    #[test]
+    fn loaders_need_a_launcher_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("version_manifest.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "latest": {"release": "1.20.1", "snapshot": "1.20.1"},
+                "versions": [{
+                    "id": "1.20.1",
+                    "releaseTime": "2023-06-12T13:25:51+00:00",
+                    "time": "2023-06-12T13:25:51+00:00",
+                    "type": "release",
+                    "url": "http://127.0.0.1:1/1.20.1.json"
+                }]
+            }"#,
+        )
+        .unwrap();
+        let downloader = ClientDownloader::from_cached(&path).unwrap();
+
+        let result = downloader.download_version(
+            "1.20.1",
+            &dir.path().join("game"),
+            &dir.path().join("base"),
+            None,
+            None,
+            Some(Launcher::Fabric),
+            None,
+            None,
+        );
+        assert!(matches!(
+            result,
+            Err(ClientDownloaderError::MissingLauncherId(Launcher::Fabric))
+        ));
+    }
+
+    #[test]
+    fn installed_versions_need_a_jar() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().to_path_buf();
+        for id in ["1.20.1", "1.8.9"] {
+            let version_dir = base_path.join("versions").join(id);
+            std::fs::create_dir_all(&version_dir).unwrap();
+            std::fs::write(version_dir.join(format!("{id}.jar")), "").unwrap();
         }
+        std::fs::create_dir_all(base_path.join("versions/broken")).unwrap();
 
-        Ok(results)
+        assert_eq!(
+            ClientDownloader::installed_versions(&base_path),
+            ["1.20.1", "1.8.9"]
+        );
+    }
+
+    #[test]
+    fn only_all_failed_results_are_an_error() {
+        let failed = || Err(DownloadError::Download(DownloadOutput::default()));
+
+        assert!(check_results(Vec::new()).unwrap().is_empty());
+        assert_eq!(
+            check_results(vec![Ok(DownloadOutput::default()), failed()])
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(matches!(
+            check_results(vec![failed(), failed()]),
+            Err(ClientDownloaderError::AllDownloadsFailed(errors)) if errors.len() == 2
+        ));
+    }
+
+    #[test]
+    fn stale_cached_manifests_are_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("1.20.1.json");
+        std::fs::write(&path, "{}").unwrap();
+
+        let version = |sha1: Option<&str>| {
+            serde_json::from_value(json!({
+                "id": "1.20.1",
+                "type": "release",
+                "url": "",
+                "time": "",
+                "releaseTime": "",
+                "sha1": sha1
+            }))
+            .unwrap()
+        };
+
+        // sha1 of "{}"
+        let sha1 = "bf21a9e8fbc5a3846fb05b4fa0859e0917b2202f";
+        assert!(read_cached_manifest(&path, &version(Some(sha1))).is_some());
+        assert!(read_cached_manifest(&path, &version(Some(&"0".repeat(40)))).is_none());
+        assert!(read_cached_manifest(&path, &version(None)).is_some());
+    }
+
+    #[test]
+    fn timeouts_have_their_own_error() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let client = reqwest::blocking::Client::builder()
+            .timeout(std::time::Duration::from_millis(100))
+            .build()
+            .unwrap();
+
+        let error = client.get(url).send().unwrap_err();
+        assert!(matches!(
+            ClientDownloaderError::from(error),
+            ClientDownloaderError::Timeout(_)
+        ));
+    }
+
+    #[test]
+    fn libraries_are_excluded_by_coordinate_prefix() {
+        let excluded = [
+            "org.lwjgl:lwjgl".to_string(),
+            "com.mojang:brigadier:1.0.18".to_string(),
+        ];
+
+        assert!(library_excluded("org.lwjgl:lwjgl:3.3.1", &excluded));
+        assert!(library_excluded(
+            "org.lwjgl:lwjgl:3.3.1:natives-linux",
+            &excluded
+        ));
+        assert!(!library_excluded("org.lwjgl:lwjgl-glfw:3.3.1", &excluded));
+        assert!(library_excluded("com.mojang:brigadier:1.0.18", &excluded));
+        assert!(!library_excluded("com.mojang:brigadier:1.1.8", &excluded));
+    }
+
+    #[test]
+    fn versions_are_downloaded_from_a_mock_server() {
+        let sha1 = |bytes: &str| {
+            chksum::sha1::chksum(bytes.as_bytes())
+                .unwrap()
+                .to_hex_lowercase()
+        };
+        let mut server = mockito::Server::new();
+        let url = server.url();
+
+        let asset_hash = sha1("asset");
+        let asset_index =
+            json!({"objects": {"icon.png": {"hash": asset_hash, "size": 5}}}).to_string();
+        let file = |path: &str, body: &str| json!({"path": path, "sha1": sha1(body), "size": body.len(), "url": format!("{url}/{path}")});
+        let manifest = json!({
+            "arguments": {},
+            "assetIndex": {"id": "5", "sha1": sha1(&asset_index), "size": asset_index.len(), "totalSize": 5, "url": format!("{url}/5.json")},
+            "assets": "5",
+            "complianceLevel": 1,
+            "downloads": {"client": file("client.jar", "client"), "server": file("server.jar", "server")},
+            "id": "1.20.1",
+            "javaVersion": {"component": "java-runtime-gamma", "majorVersion": 17},
+            "libraries": [{"downloads": {"artifact": file("a/a/1/a-1.jar", "library")}, "name": "a:a:1"}],
+            "logging": {"client": {"argument": "", "file": {"id": "", "sha1": "", "size": 1, "url": ""}, "type": ""}},
+            "mainClass": "net.minecraft.client.main.Main",
+            "minimumLauncherVersion": 21,
+            "releaseTime": "2023-06-12T13:25:51+00:00",
+            "time": "2023-06-12T13:25:51+00:00",
+            "type": "release"
+        })
+        .to_string();
+        let version_manifest = json!({
+            "latest": {"release": "1.20.1", "snapshot": "1.20.1"},
+            "versions": [{
+                "id": "1.20.1",
+                "type": "release",
+                "url": format!("{url}/1.20.1.json"),
+                "time": "2023-06-12T13:25:51+00:00",
+                "releaseTime": "2023-06-12T13:25:51+00:00",
+                "sha1": sha1(&manifest)
+            }]
+        })
+        .to_string();
+
+        let mocks = [
+            ("/version_manifest_v2.json", version_manifest),
+            ("/1.20.1.json", manifest),
+            ("/5.json", asset_index),
+            ("/client.jar", "client".to_string()),
+            ("/a/a/1/a-1.jar", "library".to_string()),
+            (
+                &format!("/{}/{asset_hash}", &asset_hash[..2]),
+                "asset".to_string(),
+            ),
+        ]
+        .map(|(path, body)| {
+            // The asset index is both read and saved
+            server
+                .mock("GET", path)
+                .with_body(body)
+                .expect_at_least(1)
+                .create()
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let game_path = dir.path().join("game");
+        let base_path = dir.path().join(".minecraft");
+        let mut downloader =
+            ClientDownloader::new_from_url(&format!("{url}/version_manifest_v2.json")).unwrap();
+        downloader.with_mirror(crate::client::MirrorConfig {
+            assets: Some(url.clone()),
+            ..Default::default()
+        });
+        let installed = downloader
+            .download_version(
+                "1.20.1", &game_path, &base_path, None, None, None, None, None,
+            )
+            .unwrap();
+
+        for mock in mocks {
+            mock.assert();
+        }
+        assert!(installed
+            .results
+            .iter()
+            .all(|result| result.as_ref().unwrap().verified == crate::client::VerifyStatus::Ok));
+        for path in [
+            "versions/1.20.1/1.20.1.jar",
+            "libraries/a/a/1/a-1.jar",
+            "assets/indexes/5.json",
+            &format!("assets/objects/{}/{asset_hash}", &asset_hash[..2]),
+        ] {
+            assert!(base_path.join(path).is_file(), "{path} is missing");
+        }
+    }
+
+    #[test]
+    fn custom_manifests_are_downloaded_without_the_version_list() {
+        let mut server = mockito::Server::new();
+        let url = server.url();
+        let client = server
+            .mock("GET", "/client.jar")
+            .with_body("client")
+            .create();
+        let _asset_index = server
+            .mock("GET", "/5.json")
+            .with_body(r#"{"objects": {}}"#)
+            .create();
+
+        let mut manifest = test_manifest();
+        manifest.id = "pack".to_string();
+        manifest.libraries.clear();
+        manifest.logging.client.file.url = String::new();
+        manifest.asset_index.url = format!("{url}/5.json");
+        manifest.asset_index.sha1 = String::new();
+        manifest.downloads.client.url = format!("{url}/client.jar");
+        manifest.downloads.client.sha1 = String::new();
+
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().join(".minecraft");
+        let downloader = ClientDownloader::from_manifest(
+            serde_json::from_value(json!({
+                "latest": {"release": "", "snapshot": ""},
+                "versions": []
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        let installed = downloader
+            .download_version_from_manifest(
+                manifest,
+                &dir.path().join("game"),
+                &base_path,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+
+        client.assert();
+        assert_eq!(installed.manifest.id, "pack");
+        assert!(base_path.join("versions/pack/pack.jar").is_file());
+        assert!(dir.path().join("game/manifest.json").is_file());
+    }
+
+    #[test]
+    fn unstable_fabric_loaders_are_filtered() {
+        let mut server = mockito::Server::new();
+        let loader = |version: &str, build: i32, stable: bool| json!({"loader": {"separator": ".", "build": build, "maven": "", "version": version, "stable": stable}});
+        let _loaders = server
+            .mock("GET", "/v2/versions/loader/1.20.1/")
+            .with_body(
+                json!([
+                    loader("0.16.11", 11, false),
+                    loader("0.16.10", 10, true),
+                    loader("0.14.21", 21, true)
+                ])
+                .to_string(),
+            )
+            .expect_at_least(1)
+            .create();
+
+        let mut downloader = ClientDownloader::from_manifest(
+            serde_json::from_value(json!({
+                "latest": {"release": "", "snapshot": ""},
+                "versions": []
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        downloader.with_mirror(crate::client::MirrorConfig {
+            fabric_meta: Some(server.url()),
+            ..Default::default()
+        });
+
+        let stable: Vec<String> = downloader
+            .get_stable_fabric_loader_versions("1.20.1")
+            .unwrap()
+            .into_iter()
+            .map(|loader| loader.loader.version)
+            .collect();
+        assert_eq!(stable, ["0.16.10", "0.14.21"]);
+        assert_eq!(
+            downloader
+                .latest_stable_fabric_loader("1.20.1")
+                .unwrap()
+                .loader
+                .version,
+            "0.16.10"
+        );
+    }
+
+    #[test]
+    fn unchanged_version_lists_are_read_from_the_cache() {
+        let mut server = mockito::Server::new();
+        let body = json!({
+            "latest": {"release": "1.20.1", "snapshot": "1.20.1"},
+            "versions": [{"id": "1.20.1", "type": "release", "url": "", "time": "", "releaseTime": ""}]
+        })
+        .to_string();
+        let fetched = server
+            .mock("GET", "/version_manifest_v2.json")
+            .match_header("if-none-match", mockito::Matcher::Missing)
+            .with_header("etag", "\"v1\"")
+            .with_body(body)
+            .expect(2)
+            .create();
+        let not_modified = server
+            .mock("GET", "/version_manifest_v2.json")
+            .match_header("if-none-match", "\"v1\"")
+            .with_status(304)
+            .create();
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut downloader =
+            ClientDownloader::new_from_url(&format!("{}/version_manifest_v2.json", server.url()))
+                .unwrap();
+        downloader.with_manifest_cache(dir.path().to_path_buf());
+        downloader.refresh_versions().unwrap();
+        let refreshed = downloader.refresh_versions().unwrap();
+
+        assert_eq!(refreshed.latest.release, "1.20.1");
+        fetched.assert();
+        not_modified.assert();
+    }
+
+    #[test]
+    fn error_statuses_are_reported() {
+        let mut server = mockito::Server::new();
+        let _missing = server
+            .mock("GET", "/version_manifest_v2.json")
+            .with_status(404)
+            .create();
+
+        let url = format!("{}/version_manifest_v2.json", server.url());
+        let error = ClientDownloader::new_from_url(&url).unwrap_err();
+
+        assert!(matches!(
+            error,
+            ClientDownloaderError::HttpStatus { url: failed, status }
+                if failed == url && status == reqwest::StatusCode::NOT_FOUND
+        ));
     }
 }
@@ -1,15 +1,19 @@
 use futures::stream::{self, StreamExt};
 use reqwest::Client;
 use std::fs::create_dir_all;
-use std::io::{Seek, SeekFrom, Write};
+use std::io::Write;
 use std::path::PathBuf;
-use std::time::Duration;
-use tokio::task::JoinError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chksum::sha1;
 
 use crate::error::DownloadError;
 use crate::manifest::ManifestFile;
 
-use super::{verify, DownloadOutput, DownloadResult, Progress, VerifyStatus};
+use super::client_downloader::USER_AGENT;
+use super::{verify, DownloadOutput, DownloadResult, Progress, ProgressEvent, VerifyStatus};
 
 #[derive(Clone, Debug)]
 pub struct DownloadData {
@@ -20,153 +24,473 @@ pub struct DownloadData {
     pub(crate) total_size: u64,
 }
 
-#[derive(Clone)]
+/// Downloads any list of files, not only the ones of a manifest, e.g. mods:
+/// each `DownloadData` is saved to its `output_path` in the download folder,
+/// creating the missing directories, and checked against its SHA-1.
+#[derive(Clone, Debug)]
 pub struct DownloaderService {
-    client: Client,
+    /// `None` until `with_client`, a client is then built for each run.
+    client: Option<Client>,
     downloads: Vec<DownloadData>,
-    parallel_requests: u16,
+    parallel_requests: usize,
+    retries: u16,
+    resume: bool,
+    download_folder: PathBuf,
+    cancel: Option<Arc<AtomicBool>>,
+    cleanup_on_cancel: bool,
+    max_bytes_per_sec: u64,
+    buffer_size: usize,
+    proxy: Option<reqwest::Proxy>,
+    accept_invalid_certs: bool,
+}
+
+/// What every single download of a `DownloaderService::run` shares.
+#[derive(Clone)]
+struct DownloadSettings {
+    client: Client,
     retries: u16,
+    resume: bool,
     download_folder: PathBuf,
+    cancel: Option<Arc<AtomicBool>>,
+    cleanup_on_cancel: bool,
+    throttle: Option<Arc<Throttle>>,
+    buffer_size: usize,
+}
+
+/// Bandwidth limit shared by all the downloads of a run.
+struct Throttle {
+    max_bytes_per_sec: u64,
+    started: Instant,
+    transferred: Mutex<u64>,
+}
+
+impl Throttle {
+    fn new(max_bytes_per_sec: u64) -> Self {
+        Self {
+            max_bytes_per_sec,
+            started: Instant::now(),
+            transferred: Mutex::new(0),
+        }
+    }
+
+    /// Accounts for `bytes` more and waits until they fit into the limit.
+    async fn consume(&self, bytes: u64) {
+        let due = {
+            let mut transferred = self.transferred.lock().unwrap();
+            *transferred += bytes;
+            self.started
+                + Duration::from_secs_f64(*transferred as f64 / self.max_bytes_per_sec as f64)
+        };
+        tokio::time::sleep_until(due.into()).await;
+    }
 }
 
-fn file_name_from_url(url: &str) -> std::path::PathBuf {
-    if url.is_empty() {
-        return std::path::PathBuf::new();
+impl DownloadSettings {
+    fn cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
     }
-    let Ok(url) = reqwest::Url::parse(url) else { return std::path::PathBuf::new() };
+}
 
-    url.path_segments()
-        .map_or_else(std::path::PathBuf::new, |f| {
-            std::path::PathBuf::from(f.last().unwrap_or(""))
+/// The last path segment of `url`, empty if there is none.
+fn file_name_from_url(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            url.path_segments()
+                .and_then(|mut segments| segments.next_back().map(str::to_string))
         })
+        .unwrap_or_default()
+}
+
+/// How much of a file is buffered in memory before it is written to disk.
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Delay before the first retry, doubled after every failed attempt.
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+pub(super) fn report(progress: &Option<Progress>, event: ProgressEvent) {
+    if let Some(progress) = progress.as_ref() {
+        progress.lock().unwrap().event(event);
+    }
+}
+
+/// Ends the download of a cancelled file, with an event like every other
+/// outcome so listeners can count it as finished.
+fn cancel(
+    progress: &Option<Progress>,
+    result: DownloadOutput,
+) -> Result<DownloadOutput, DownloadError> {
+    report(
+        progress,
+        ProgressEvent::FileCancelled {
+            file_name: result.file_name.clone(),
+        },
+    );
+    Err(DownloadError::Cancelled(result))
 }
 
+/// A finished request of `download_url`.
+struct Transfer {
+    status: reqwest::StatusCode,
+    /// The SHA-1 of the file, hashed while it was written. `None` when it
+    /// wasn't written from the start or there is no hash to compare it to.
+    sha1: Option<String>,
+}
+
+/// Streams the file into `output_path`, continuing after the first `offset`
+/// bytes when the server supports ranges.
+///
+/// `Ok(None)` means the transfer itself failed or was cancelled. `written`
+/// is set once the file is opened. `reported` is how much of the file was
+/// reported as progress by earlier attempts.
 async fn download_url(
-    client: reqwest::Client,
-    url: String,
-    writer: &mut std::io::BufWriter<std::fs::File>,
-    progress_opt: Option<Progress>,
-) -> u16 {
-    let Some(progress) = progress_opt else { return reqwest::StatusCode::NOT_IMPLEMENTED.as_u16() };
-    if let Ok(mut response) = client.get(&url).send().await {
-        let mut current: u64 = 0;
-        writer.seek(SeekFrom::Start(current)).unwrap_or(0);
-
-        while let Some(bytes) = response.chunk().await.unwrap_or(None) {
-            if writer.write_all(&bytes).is_err() {}
-
-            current += bytes.len() as u64;
-            progress.lock().unwrap().progress(bytes.len() as u64);
-        }
+    settings: &DownloadSettings,
+    download: &DownloadData,
+    output_path: &PathBuf,
+    offset: u64,
+    progress: Option<Progress>,
+    written: &mut bool,
+    reported: &mut u64,
+) -> std::io::Result<Option<Transfer>> {
+    let mut request = settings.client.get(&download.url);
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={offset}-"));
+    }
+    let Ok(mut response) = request.send().await else {
+        return Ok(None);
+    };
+    if !response.status().is_success() {
+        return Ok(Some(Transfer {
+            status: response.status(),
+            sha1: None,
+        }));
+    }
 
-        response.status().as_u16()
+    // A plain 200 means the server ignored the range, start over
+    let (file, mut current) = if response.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+        let file = std::fs::OpenOptions::new().append(true).open(output_path)?;
+        (file, offset)
     } else {
-        reqwest::StatusCode::BAD_REQUEST.as_u16()
+        (std::fs::File::create(output_path)?, 0)
+    };
+    *written = true;
+    let mut writer = std::io::BufWriter::with_capacity(settings.buffer_size, file);
+    // A continued file would need its start read again, it's verified from
+    // disk instead
+    let mut hasher = (current == 0 && !download.sha1.trim().is_empty()).then(sha1::new);
+
+    loop {
+        if settings.cancelled() {
+            writer.flush()?;
+            return Ok(None);
+        }
+
+        match response.chunk().await {
+            Ok(Some(bytes)) => {
+                writer.write_all(&bytes)?;
+                current += bytes.len() as u64;
+                if let Some(hasher) = hasher.as_mut() {
+                    hasher.update(&bytes);
+                }
+
+                if let Some(throttle) = settings.throttle.as_ref() {
+                    throttle.consume(bytes.len() as u64).await;
+                }
+
+                if let Some(progress) = progress.as_ref() {
+                    let mut progress = progress.lock().unwrap();
+                    // Files of unknown size aren't part of the total either. A
+                    // restarted attempt only adds what goes past earlier ones
+                    let counted = current.min(download.total_size);
+                    if counted > *reported {
+                        progress.progress(counted - *reported);
+                        *reported = counted;
+                    }
+                    progress.event(ProgressEvent::BytesDownloaded {
+                        file_name: download.file_name.clone(),
+                        current,
+                        total: download.total_size,
+                    });
+                }
+            }
+            Ok(None) => break,
+            Err(_) => {
+                writer.flush()?;
+                return Ok(None);
+            }
+        }
+    }
+    writer.flush()?;
+
+    Ok(Some(Transfer {
+        status: response.status(),
+        sha1: hasher.map(|hasher| hasher.digest().to_hex_lowercase()),
+    }))
+}
+
+/// Verifies an already downloaded copy of the file, `None` if there is no
+/// valid one. Files without a known hash fall back to comparing the size.
+fn downloaded_status(download: &DownloadData, output_path: &PathBuf) -> Option<VerifyStatus> {
+    let metadata = output_path.metadata().ok().filter(|m| m.is_file())?;
+
+    match verify::verify_file(download.sha1.as_str(), output_path.clone()) {
+        VerifyStatus::Failed => None,
+        VerifyStatus::NotVerified
+            if download.total_size == 0 || metadata.len() != download.total_size =>
+        {
+            None
+        }
+        status => Some(status),
     }
 }
 
 async fn download(
-    client: Client,
+    settings: DownloadSettings,
     download: DownloadData,
-    retries: u16,
-    download_folder: PathBuf,
     progress: Option<Progress>,
 ) -> Result<DownloadOutput, DownloadError> {
-    let mut download_successful = false;
-    let mut output_path = download_folder.clone();
-    output_path.push(download.output_path);
+    let mut output_path = settings.download_folder.clone();
+    output_path.push(&download.output_path);
 
     let mut result = DownloadOutput {
         status: reqwest::StatusCode::OK.as_u16(),
         // @TODO
         file_name: download.file_name.clone(),
+        url: download.url.clone(),
         file_path: output_path.clone(),
         verified: VerifyStatus::NotVerified,
+        attempts: 0,
+        skipped: false,
+        bytes: 0,
+        written: false,
     };
 
-    if output_path.exists() && output_path.is_file() {
-        if let Ok(metadata) = output_path.metadata() {
-            if metadata.len() == download.total_size {
-                return Ok(result);
-            }
-        }
+    if settings.cancelled() {
+        return cancel(&progress, result);
     }
 
-    create_dir_all(output_path.parent().unwrap())
-        .map_err(|e| DownloadError::Setup(e.to_string()))?;
+    if let Some(verified) = downloaded_status(&download, &output_path) {
+        result.skipped = true;
+        result.verified = verified;
+        if let Some(progress) = progress.as_ref() {
+            progress.lock().unwrap().progress(download.total_size);
+        }
+        report(
+            &progress,
+            ProgressEvent::FileCompleted {
+                file_name: download.file_name.clone(),
+                total: download.total_size,
+            },
+        );
+        return Ok(result);
+    }
 
-    if let Ok(file) = std::fs::OpenOptions::new()
-        .create(true)
-        .write(true)
-        .open(output_path)
-    {
-        let mut writer = std::io::BufWriter::new(file);
+    // Neither the download folder nor the directories of the file have to
+    // exist yet
+    if let Some(parent) = output_path.parent() {
+        if create_dir_all(parent).is_err() {
+            report(
+                &progress,
+                ProgressEvent::FileFailed {
+                    file_name: download.file_name.clone(),
+                },
+            );
+            return Err(DownloadError::File(result));
+        }
+    }
 
-        let url = download.url;
-        for _ in 1..=retries {
-            let s = reqwest::StatusCode::from_u16(
-                download_url(client.clone(), url.clone(), &mut writer, progress.clone()).await,
-            )
-            .unwrap_or(reqwest::StatusCode::BAD_REQUEST);
+    report(
+        &progress,
+        ProgressEvent::FileStarted {
+            file_name: download.file_name.clone(),
+            total: download.total_size,
+        },
+    );
 
-            result.status = s.as_u16();
+    let mut backoff = RETRY_BACKOFF;
+    let mut reported = 0;
+    for attempt in 0..=settings.retries {
+        if attempt > 0 {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+        // Partial files are left alone so a later run can resume them
+        if settings.cancelled() {
+            return cancel(&progress, result);
+        }
+        result.attempts = attempt + 1;
 
-            if s.is_server_error() {
-                break;
+        // Only a partial file is worth continuing, a corrupt one is not
+        let offset = match output_path.metadata() {
+            Ok(metadata)
+                if settings.resume
+                    && result.verified != VerifyStatus::Failed
+                    && metadata.len() < download.total_size =>
+            {
+                metadata.len()
             }
+            _ => 0,
+        };
 
-            if s.is_success() {
-                download_successful = true;
-                break;
-            }
+        let transfer = download_url(
+            &settings,
+            &download,
+            &output_path,
+            offset,
+            progress.clone(),
+            &mut result.written,
+            &mut reported,
+        )
+        .await;
+        let Ok(transfer) = transfer else {
+            report(
+                &progress,
+                ProgressEvent::FileFailed {
+                    file_name: download.file_name.clone(),
+                },
+            );
+            return Err(DownloadError::File(result));
+        };
+        if settings.cancelled() {
+            return cancel(&progress, result);
+        }
+        let (status, sha1) = match transfer {
+            Some(transfer) => (transfer.status, transfer.sha1),
+            None => (reqwest::StatusCode::BAD_GATEWAY, None),
+        };
+        result.status = status.as_u16();
+        if status.is_success() {
+            // Without a partial response the file was written from the start
+            let start = match status {
+                reqwest::StatusCode::PARTIAL_CONTENT => offset,
+                _ => 0,
+            };
+            let size = output_path.metadata().map(|m| m.len()).unwrap_or(start);
+            result.bytes += size.saturating_sub(start);
+        }
+
+        // The file is not there, asking again won't change that
+        if status.is_client_error() {
+            break;
+        }
+        if !status.is_success() {
+            continue;
         }
-    }
 
-    if !download_successful {
-        return Err(DownloadError::Download(result));
+        result.verified = match sha1 {
+            Some(sha1) => verify::verify_digest(&download.sha1, sha1),
+            None => verify::verify_file(download.sha1.as_str(), result.file_path.clone()),
+        };
+        if result.verified != VerifyStatus::Failed {
+            report(
+                &progress,
+                ProgressEvent::FileCompleted {
+                    file_name: download.file_name.clone(),
+                    total: download.total_size,
+                },
+            );
+            return Ok(result);
+        }
     }
 
-    result.verified = if !download.sha1.is_empty() {
-        verify::verify_file(download.sha1.as_str(), result.file_path.clone())
-    } else {
-        VerifyStatus::Ok
-    };
+    report(
+        &progress,
+        ProgressEvent::FileFailed {
+            file_name: download.file_name.clone(),
+        },
+    );
 
-    // Ignoring verification
     if result.verified == VerifyStatus::Failed {
         return Err(DownloadError::Verification(result));
     }
 
-    Ok(result)
+    Err(DownloadError::Download(result))
+}
+
+/// `download`, with the file removed if this run wrote to it but didn't
+/// complete and `settings.cleanup_on_cancel` is set.
+async fn download_or_clean_up(
+    settings: DownloadSettings,
+    download_data: DownloadData,
+    progress: Option<Progress>,
+) -> Result<DownloadOutput, DownloadError> {
+    let cleanup = settings.cleanup_on_cancel;
+    let result = download(settings, download_data, progress).await;
+
+    // A file that was never opened isn't from this run
+    if let Some(output) = result
+        .as_ref()
+        .err()
+        .and_then(DownloadError::output)
+        .filter(|output| cleanup && output.written)
+    {
+        // Best effort, the result of the file is what matters to the caller
+        let _ = std::fs::remove_file(&output.file_path);
+    }
+    result
 }
 
 impl DownloadData {
+    /// A download of `url` to `path`, relative to the download folder, named
+    /// after the last segment of the url. Without a hash and a size.
     pub fn new(url: &str, path: &str) -> Self {
         Self {
             url: url.to_string(),
-            file_name: file_name_from_url(url)
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
+            file_name: file_name_from_url(url),
             output_path: path.to_string(),
             sha1: String::new(),
             total_size: 0,
         }
     }
+
+    /// The SHA-1 the file is verified against.
+    pub fn with_sha1(mut self, sha1: &str) -> Self {
+        self.sha1 = sha1.to_string();
+        self
+    }
+
+    /// The name reported in the progress events and results.
+    pub fn with_file_name(mut self, file_name: &str) -> Self {
+        self.file_name = file_name.to_string();
+        self
+    }
+
+    /// The expected size, for the progress and to resume partial files.
+    pub fn with_total_size(mut self, total_size: u64) -> Self {
+        self.total_size = total_size;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    /// Where the file goes, relative to the download folder.
+    pub fn output_path(&self) -> &str {
+        &self.output_path
+    }
+
+    pub fn sha1(&self) -> &str {
+        &self.sha1
+    }
+
+    /// The expected size, 0 when unknown.
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
 }
 
 impl From<ManifestFile> for DownloadData {
     fn from(manifest: ManifestFile) -> Self {
         Self {
             url: manifest.url.clone(),
-            file_name: file_name_from_url(&manifest.url)
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
+            file_name: file_name_from_url(&manifest.url),
             output_path: manifest.path.unwrap_or_default(),
             sha1: manifest.sha1,
             total_size: manifest.size,
@@ -174,23 +498,28 @@ impl From<ManifestFile> for DownloadData {
     }
 }
 
+fn client_builder() -> reqwest::ClientBuilder {
+    Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(Duration::from_secs(30))
+        .timeout(Duration::from_secs(300))
+}
+
 impl Default for DownloaderService {
     fn default() -> Self {
         Self {
-            client: Client::builder()
-                .user_agent(format!(
-                    "{}/{}",
-                    env!("CARGO_PKG_NAME"),
-                    env!("CARGO_PKG_VERSION")
-                ))
-                .connect_timeout(Duration::from_secs(30))
-                .timeout(Duration::from_secs(300))
-                .build()
-                .unwrap(),
+            client: None,
             downloads: Vec::new(),
             parallel_requests: 32,
             retries: 3,
+            resume: false,
             download_folder: Default::default(),
+            cancel: None,
+            cleanup_on_cancel: false,
+            max_bytes_per_sec: 0,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            proxy: None,
+            accept_invalid_certs: false,
         }
     }
 }
@@ -198,81 +527,547 @@ impl Default for DownloaderService {
 impl DownloaderService {
     pub fn new(download_folder: PathBuf) -> Self {
         Self {
-            download_folder: download_folder,
+            download_folder,
             ..Default::default()
         }
     }
 
+    /// Downloads with `client` as is, `with_proxy` and
+    /// `with_danger_accept_invalid_certs` only apply to the default client.
     pub fn with_client(&mut self, client: Client) -> &mut Self {
-        self.client = client;
+        self.client = Some(client);
+        self
+    }
+
+    /// Routes every download through `proxy`.
+    pub fn with_proxy(&mut self, proxy: reqwest::Proxy) -> &mut Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Accepts any TLS certificate, even self-signed or expired ones. Off by
+    /// default.
+    ///
+    /// DANGEROUS: anyone on the network path can then serve the files. Only
+    /// meant for mirrors on a trusted network.
+    pub fn with_danger_accept_invalid_certs(&mut self, accept: bool) -> &mut Self {
+        self.accept_invalid_certs = accept;
         self
     }
 
+    /// The client given to `with_client`, or the default one with the proxy
+    /// and the certificate setting.
+    fn client(&self) -> Result<Client, DownloadError> {
+        if let Some(client) = &self.client {
+            return Ok(client.clone());
+        }
+
+        let mut builder = client_builder().danger_accept_invalid_certs(self.accept_invalid_certs);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        builder
+            .build()
+            .map_err(|e| DownloadError::Setup(e.to_string()))
+    }
+
     pub fn with_downloads(&mut self, downloads: Vec<DownloadData>) -> &mut Self {
         self.downloads = downloads;
         self
     }
 
     pub fn with_parallel_requests(&mut self, parallel_requests: u16) -> &mut Self {
-        self.parallel_requests = parallel_requests;
+        self.with_concurrency(parallel_requests as usize)
+    }
+
+    /// Sets how many files are downloaded at the same time.
+    ///
+    /// Each download runs as its own task, a failing one doesn't stop the
+    /// others and ends up as an `Err` in the results.
+    pub fn with_concurrency(&mut self, concurrency: usize) -> &mut Self {
+        self.parallel_requests = concurrency.max(1);
         self
     }
 
+    /// Sets how many times a failed download is retried, waiting twice as
+    /// long before each new attempt.
     pub fn with_retries(&mut self, retries: u16) -> &mut Self {
         self.retries = retries;
         self
     }
 
+    /// Continues partially downloaded files with a `Range` request instead
+    /// of downloading them again from the start.
+    pub fn with_resume(&mut self, resume: bool) -> &mut Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Stops the download once `cancel` is set: files not finished by then
+    /// end up as `DownloadError::Cancelled`, partial ones stay on disk.
+    pub fn with_cancel(&mut self, cancel: Arc<AtomicBool>) -> &mut Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Deletes the files that were being written when the download was
+    /// cancelled or failed, instead of leaving them to be resumed. Files
+    /// completed before that are kept.
+    pub fn with_cleanup_on_cancel(&mut self, cleanup: bool) -> &mut Self {
+        self.cleanup_on_cancel = cleanup;
+        self
+    }
+
+    /// Caps the combined speed of all the downloads, `0` means unlimited.
+    pub fn with_max_bytes_per_sec(&mut self, limit: u64) -> &mut Self {
+        self.max_bytes_per_sec = limit;
+        self
+    }
+
+    /// Sets how many bytes of a file are buffered before they are written
+    /// to disk, `DEFAULT_BUFFER_SIZE` by default.
+    ///
+    /// The responses are streamed chunk by chunk, a file is never held in
+    /// memory as a whole whatever its size.
+    pub fn with_buffer_size(&mut self, buffer_size: usize) -> &mut Self {
+        self.buffer_size = buffer_size.max(1);
+        self
+    }
+
     pub fn with_download_folder(&mut self, download_folder: PathBuf) -> &mut Self {
         self.download_folder = download_folder;
         self
     }
 
-    pub fn run(&self, progress: Option<Progress>) -> Result<Vec<DownloadResult>, JoinError> {
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        let cl = self.client.clone();
-        let max = self
-            .downloads
-            .clone()
-            .iter()
-            .map(|d| d.total_size)
-            .reduce(|accum, i| accum + i)
-            .unwrap_or_default();
-
-        let download_folder = self.download_folder.clone();
+    /// The summed size of the queued downloads, without downloading anything.
+    ///
+    /// Files whose size isn't known (`total_size` of 0) count as 0 bytes, so
+    /// this is a lower bound when there are any.
+    pub fn estimated_total_bytes(&self) -> u64 {
+        self.downloads.iter().map(|d| d.total_size).sum()
+    }
+
+    /// Downloads every file, failed files are errors in the results.
+    ///
+    /// There is one result per queued download, in the same order, whether
+    /// it was downloaded, skipped, failed or cancelled. Only failing to run
+    /// the downloads at all is an `Err`.
+    pub fn run(&self, progress: Option<Progress>) -> Result<Vec<DownloadResult>, DownloadError> {
+        let rt = tokio::runtime::Runtime::new().map_err(DownloadError::Runtime)?;
+        rt.block_on(self.run_async(progress))
+    }
+
+    /// Same as `run` but on the runtime of the caller.
+    pub async fn run_async(
+        &self,
+        progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, DownloadError> {
+        let max = self.estimated_total_bytes();
+
+        let settings = DownloadSettings {
+            client: self.client()?,
+            retries: self.retries,
+            resume: self.resume,
+            download_folder: self.download_folder.clone(),
+            cancel: self.cancel.clone(),
+            cleanup_on_cancel: self.cleanup_on_cancel,
+            throttle: (self.max_bytes_per_sec > 0)
+                .then(|| Arc::new(Throttle::new(self.max_bytes_per_sec))),
+            buffer_size: self.buffer_size,
+        };
         let downloads = self.downloads.clone();
-        let retries = self.retries;
         let parallel_requests = self.parallel_requests;
         let progress = progress.clone();
 
-        if progress.is_some() {
-            progress.as_ref().unwrap().lock().unwrap().setup(max);
+        if let Some(progress) = progress.as_ref() {
+            let mut progress = progress.lock().unwrap();
+            progress.setup(max);
+            progress.setup_files(downloads.len() as u64);
         }
 
-        let result = rt.spawn(async move {
+        Ok(tokio::spawn(async move {
             let progress = progress.clone();
             let res = {
                 stream::iter(downloads)
                     .map(|d| {
-                        download(
-                            cl.clone(),
+                        let failed = DownloadOutput {
+                            file_name: d.file_name.clone(),
+                            url: d.url.clone(),
+                            file_path: settings.download_folder.join(&d.output_path),
+                            ..Default::default()
+                        };
+                        let task = tokio::spawn(download_or_clean_up(
+                            settings.clone(),
                             d,
-                            retries,
-                            download_folder.clone(),
                             progress.clone(),
-                        )
+                        ));
+                        async move { task.await.unwrap_or(Err(DownloadError::Download(failed))) }
                     })
-                    .buffered(parallel_requests as usize)
+                    .buffered(parallel_requests)
                     .collect::<Vec<DownloadResult>>()
                     .await
             };
 
-            if progress.is_some() {
-                progress.unwrap().lock().unwrap().done();
+            if let Some(progress) = progress {
+                progress.lock().unwrap().done();
             }
             res
+        })
+        .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::{Arc, Mutex};
+
+    use super::{DownloadData, DownloaderService, Throttle, VerifyStatus};
+    use crate::client::{ProgressEvent, ProgressState, Reporter};
+    use crate::error::DownloadError;
+
+    #[derive(Default)]
+    struct Events(Vec<ProgressEvent>);
+
+    impl Reporter for Events {
+        fn event(&mut self, event: ProgressEvent) {
+            self.0.push(event);
+        }
+    }
+
+    #[derive(Default)]
+    struct Bytes(u64);
+
+    impl Reporter for Bytes {
+        fn progress(&mut self, current: u64) {
+            self.0 += current;
+        }
+    }
+
+    /// Answers a single request with `body`, returns the url to request.
+    fn serve_once(body: &'static str) -> String {
+        serve_once_with("200 OK", body)
+    }
+
+    fn serve_once_with(status: &'static str, body: &'static str) -> String {
+        serve_each(status, vec![body])
+    }
+
+    /// Answers one request per body, in order.
+    fn serve_each(status: &'static str, bodies: Vec<&'static str>) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            for body in bodies {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).unwrap();
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
         });
 
-        futures::executor::block_on(result)
+        url
+    }
+
+    #[test]
+    fn failed_downloads_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let results = DownloaderService::new(dir.path().to_path_buf())
+            .with_downloads(vec![
+                DownloadData::new("http://127.0.0.1:1/a.jar", "a.jar"),
+                DownloadData::new("http://127.0.0.1:1/b.jar", "b.jar"),
+            ])
+            .with_concurrency(2)
+            .with_retries(1)
+            .run(None)
+            .unwrap();
+
+        assert_eq!(results.len(), 2);
+        for result in results {
+            match result {
+                Err(DownloadError::Download(output)) => assert_eq!(output.attempts, 2),
+                _ => panic!("the download should have failed"),
+            }
+        }
+    }
+
+    #[test]
+    fn valid_files_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("hello.txt"), "hello").unwrap();
+
+        let results = DownloaderService::new(dir.path().to_path_buf())
+            .with_downloads(vec![DownloadData::new(
+                "http://127.0.0.1:1/hello.txt",
+                "hello.txt",
+            )
+            .with_sha1("aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d")])
+            .run(None)
+            .unwrap();
+
+        let output = results[0].as_ref().unwrap();
+        assert!(output.skipped);
+        assert_eq!(output.verified, VerifyStatus::Ok);
+    }
+
+    #[test]
+    fn file_events_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let events = Arc::new(Mutex::new(Events::default()));
+
+        DownloaderService::new(dir.path().to_path_buf())
+            .with_downloads(vec![DownloadData::new("http://127.0.0.1:1/a.jar", "a.jar")])
+            .with_retries(0)
+            .run(Some(events.clone()))
+            .unwrap();
+
+        let events = &events.lock().unwrap().0;
+        assert!(matches!(
+            events.as_slice(),
+            [ProgressEvent::FileStarted { file_name, .. }, ProgressEvent::FileFailed { .. }]
+                if file_name == "a.jar"
+        ));
+    }
+
+    #[test]
+    fn unknown_sizes_count_as_zero() {
+        let service = DownloaderService::new(Default::default())
+            .with_downloads(vec![
+                DownloadData::new("http://127.0.0.1:1/a.jar", "a.jar").with_total_size(312),
+                DownloadData::new("http://127.0.0.1:1/b.jar", "b.jar"),
+            ])
+            .clone();
+
+        assert_eq!(service.estimated_total_bytes(), 312);
+    }
+
+    #[test]
+    fn cancelled_downloads_are_not_started() {
+        let dir = tempfile::tempdir().unwrap();
+        let results = DownloaderService::new(dir.path().to_path_buf())
+            .with_downloads(vec![DownloadData::new("http://127.0.0.1:1/a.jar", "a.jar")])
+            .with_cancel(Arc::new(AtomicBool::new(true)))
+            .run(None)
+            .unwrap();
+
+        match &results[0] {
+            Err(DownloadError::Cancelled(output)) => assert_eq!(output.attempts, 0),
+            _ => panic!("the download should have been cancelled"),
+        }
+    }
+
+    #[test]
+    fn cancelled_downloads_are_finished() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(Mutex::new(ProgressState::default()));
+
+        DownloaderService::new(dir.path().to_path_buf())
+            .with_downloads(vec![
+                DownloadData::new("http://127.0.0.1:1/a.jar", "a.jar"),
+                DownloadData::new("http://127.0.0.1:1/b.jar", "b.jar"),
+            ])
+            .with_cancel(Arc::new(AtomicBool::new(true)))
+            .run(Some(state.clone()))
+            .unwrap();
+
+        let state = state.lock().unwrap();
+        assert_eq!((state.finished_files, state.total_files), (2, 2));
+        assert_eq!(state.percentage(), 100.0);
+    }
+
+    #[test]
+    fn every_download_has_a_result() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("hello.txt"), "hello").unwrap();
+        std::fs::write(dir.path().join("file"), "").unwrap();
+
+        let results = DownloaderService::new(dir.path().to_path_buf())
+            .with_downloads(vec![
+                DownloadData::new("http://127.0.0.1:1/hello.txt", "hello.txt")
+                    .with_sha1("aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d"),
+                // The parent of the file is a file, it can't be created
+                DownloadData::new("http://127.0.0.1:1/a.jar", "file/a.jar"),
+                DownloadData::new("http://127.0.0.1:1/b.jar", "b.jar"),
+            ])
+            .with_retries(0)
+            .run(None)
+            .unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].as_ref().unwrap().skipped);
+        assert!(matches!(results[1], Err(DownloadError::File(_))));
+        let failed = results[2].as_ref().unwrap_err().output().unwrap();
+        assert!(failed.file_path.ends_with("b.jar"));
+    }
+
+    #[test]
+    fn failed_files_are_cleaned_up() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let results = DownloaderService::new(dir.path().to_path_buf())
+            .with_downloads(vec![
+                DownloadData::new(&serve_once("hello"), "hello.txt"),
+                DownloadData::new(&serve_once("world"), "world.txt").with_sha1(&"0".repeat(40)),
+            ])
+            .with_retries(0)
+            .with_cleanup_on_cancel(true)
+            .run(None)
+            .unwrap();
+
+        assert!(results[0].is_ok());
+        assert!(dir.path().join("hello.txt").is_file());
+        assert!(matches!(results[1], Err(DownloadError::Verification(_))));
+        assert!(!dir.path().join("world.txt").exists());
+    }
+
+    #[test]
+    fn files_not_written_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("hello.txt"), "old").unwrap();
+
+        let results = DownloaderService::new(dir.path().to_path_buf())
+            .with_downloads(vec![DownloadData::new(
+                &serve_once_with("404 Not Found", ""),
+                "hello.txt",
+            )
+            .with_sha1(&"0".repeat(40))])
+            .with_retries(0)
+            .with_cleanup_on_cancel(true)
+            .run(None)
+            .unwrap();
+
+        let output = results[0].as_ref().unwrap_err().output().unwrap();
+        assert_eq!(output.status, 404);
+        assert!(!output.written);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("hello.txt")).unwrap(),
+            "old"
+        );
+    }
+
+    #[test]
+    fn throttle_is_shared() {
+        let throttle = Throttle::new(1000);
+        let rt = tokio::runtime::Runtime::new().unwrap();
+
+        rt.block_on(async {
+            futures::join!(throttle.consume(100), throttle.consume(100));
+        });
+        assert!(throttle.started.elapsed() >= std::time::Duration::from_millis(200));
+    }
+
+    #[test]
+    fn unknown_sizes_are_not_reported_as_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let bytes = Arc::new(Mutex::new(Bytes::default()));
+
+        let results = DownloaderService::new(dir.path().to_path_buf())
+            .with_downloads(vec![DownloadData::new(&serve_once("hello"), "hello.txt")])
+            .run(Some(bytes.clone()))
+            .unwrap();
+
+        assert_eq!(results[0].as_ref().unwrap().bytes, 5);
+        assert_eq!(bytes.lock().unwrap().0, 0);
+    }
+
+    #[test]
+    fn retried_files_are_counted_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let bytes = Arc::new(Mutex::new(Bytes::default()));
+
+        let results = DownloaderService::new(dir.path().to_path_buf())
+            .with_downloads(vec![DownloadData::new(
+                &serve_each("200 OK", vec!["jello", "hello"]),
+                "hello.txt",
+            )
+            .with_sha1("aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d")
+            .with_total_size(5)])
+            .with_retries(1)
+            .run(Some(bytes.clone()))
+            .unwrap();
+
+        assert_eq!(results[0].as_ref().unwrap().attempts, 2);
+        assert_eq!(bytes.lock().unwrap().0, 5);
+    }
+
+    #[test]
+    fn unknown_sizes_count_files_for_the_percentage() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(Mutex::new(ProgressState::default()));
+
+        DownloaderService::new(dir.path().to_path_buf())
+            .with_downloads(vec![
+                DownloadData::new(&serve_once("hello"), "hello.txt"),
+                DownloadData::new("http://127.0.0.1:1/a.jar", "a.jar"),
+            ])
+            .with_retries(0)
+            .run(Some(state.clone()))
+            .unwrap();
+
+        let state = state.lock().unwrap();
+        assert_eq!((state.finished_files, state.total_files), (2, 2));
+        assert_eq!(state.percentage(), 100.0);
+    }
+
+    #[test]
+    fn arbitrary_files_are_downloaded() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let results = DownloaderService::new(dir.path().to_path_buf())
+            .with_downloads(vec![
+                DownloadData::new(&serve_once("hello"), "mods/sodium.jar")
+                    .with_sha1("aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d"),
+                DownloadData::new(&serve_once("world"), "config/sodium/options.json"),
+            ])
+            .run(None)
+            .unwrap();
+
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("mods/sodium.jar")).unwrap(),
+            "hello"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("config/sodium/options.json")).unwrap(),
+            "world"
+        );
+    }
+
+    #[test]
+    fn missing_download_folders_are_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("instances/modded");
+
+        let results = DownloaderService::new(root.clone())
+            .with_downloads(vec![DownloadData::new(
+                &serve_once("hello"),
+                "resourcepacks/faithful/pack.mcmeta",
+            )])
+            .run(None)
+            .unwrap();
+
+        assert!(results[0].is_ok());
+        assert!(root.join("resourcepacks/faithful/pack.mcmeta").is_file());
+    }
+
+    #[test]
+    fn downloads_are_hashed_while_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let download = DownloadData::new(&serve_once("hello"), "hello.txt")
+            .with_sha1("aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d");
+
+        let results = DownloaderService::new(dir.path().to_path_buf())
+            .with_downloads(vec![download])
+            .run(None)
+            .unwrap();
+
+        assert_eq!(results[0].as_ref().unwrap().verified, VerifyStatus::Ok);
     }
 }
@@ -0,0 +1,523 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::ClientDownloaderError;
+use crate::manifest::{AssetIndex, Manifest};
+
+use super::client_downloader::{
+    asset_downloads, library_downloads, manifest_downloads, parent_dir, version_jar_path,
+};
+use super::{
+    verify_file, ClientDownloader, DownloadData, DownloadResult, PathStrategy, Progress,
+    VerifyStatus,
+};
+
+/// How many assets `needs_download` checks.
+const ASSET_SAMPLE: usize = 16;
+
+/// What `remove_version` deleted.
+#[derive(Clone, Debug, Default)]
+pub struct RemovedVersion {
+    pub paths: Vec<PathBuf>,
+    /// The size of everything deleted.
+    pub bytes: u64,
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Option<T> {
+    let file = File::open(path).ok()?;
+    serde_json::from_reader(BufReader::new(file)).ok()
+}
+
+/// The `{id}.json` saved next to the client jar of the version.
+fn installed_manifest(
+    base_path: &Path,
+    version_id: &str,
+    paths: &dyn PathStrategy,
+) -> Option<Manifest> {
+    read_json(
+        &paths
+            .client_jar(base_path, version_id)
+            .with_extension("json"),
+    )
+}
+
+/// Every library jar the manifest may use, whatever the platform.
+fn library_files(
+    manifest: &Manifest,
+    base_path: &Path,
+    paths: &dyn PathStrategy,
+) -> HashSet<PathBuf> {
+    manifest
+        .libraries
+        .iter()
+        .flat_map(|library| {
+            let classifiers = library
+                .downloads
+                .classifiers
+                .iter()
+                .flat_map(|c| c.values());
+            library.downloads.artifact.iter().chain(classifiers)
+        })
+        .filter_map(|file| file.path.as_ref())
+        .map(|path| paths.library(base_path, path))
+        .collect()
+}
+
+fn asset_index_path(manifest: &Manifest, base_path: &Path, paths: &dyn PathStrategy) -> PathBuf {
+    paths
+        .assets(base_path)
+        .join("indexes")
+        .join(format!("{}.json", manifest.asset_index.id))
+}
+
+/// The objects of the asset index of the manifest, if it is on disk.
+fn asset_files(
+    manifest: &Manifest,
+    base_path: &Path,
+    paths: &dyn PathStrategy,
+) -> HashSet<PathBuf> {
+    let Some(asset_index) = read_json::<AssetIndex>(&asset_index_path(manifest, base_path, paths))
+    else {
+        return HashSet::new();
+    };
+
+    asset_index
+        .objects
+        .values()
+        .map(|object| object.hash.as_str())
+        .filter(|hash| hash.len() > 2)
+        .map(|hash| paths.asset_object(base_path, hash))
+        .collect()
+}
+
+fn files_of(manifest: &Manifest, base_path: &Path, paths: &dyn PathStrategy) -> HashSet<PathBuf> {
+    let mut files = library_files(manifest, base_path, paths);
+    files.extend(asset_files(manifest, base_path, paths));
+    files.insert(asset_index_path(manifest, base_path, paths));
+    files
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map(|m| m.len()).unwrap_or_default(),
+        })
+        .sum()
+}
+
+/// Checks every file of the manifest against its hash, spread over the
+/// available cores.
+fn verify_downloads(downloads: Vec<DownloadData>) -> Vec<(DownloadData, VerifyStatus)> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = downloads.len().div_ceil(threads).max(1);
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = downloads
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|download| {
+                            let path = PathBuf::from(download.output_path());
+                            let status = verify_file(download.sha1(), path);
+                            (download.clone(), status)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    })
+}
+
+/// The files of an installed version with their verification status.
+pub(super) fn installation_status(
+    manifest: &Manifest,
+    base_path: &PathBuf,
+    paths: &dyn PathStrategy,
+) -> Vec<(DownloadData, VerifyStatus)> {
+    let version_path = version_jar_path(manifest, base_path, None, paths);
+    let asset_index = read_json(&asset_index_path(manifest, base_path, paths)).unwrap_or_default();
+
+    verify_downloads(manifest_downloads(
+        manifest,
+        base_path,
+        &version_path,
+        &asset_index,
+        paths,
+    ))
+}
+
+impl ClientDownloader {
+    /// Verifies the client jar, the libraries for this platform, the asset
+    /// index and every asset of an installed version.
+    ///
+    /// Missing or corrupt files are `Failed`, files without a known hash
+    /// are `NotVerified`.
+    pub fn verify_installation(
+        &self,
+        manifest: &Manifest,
+        base_path: &PathBuf,
+    ) -> Vec<(PathBuf, VerifyStatus)> {
+        installation_status(manifest, base_path, &self.options.paths())
+            .into_iter()
+            .map(|(download, status)| (PathBuf::from(download.output_path()), status))
+            .collect()
+    }
+
+    /// Whether a version isn't ready to launch: the client jar, a library
+    /// for this platform, the asset index or a sample of the assets is
+    /// missing or corrupt.
+    ///
+    /// Stops at the first bad file, use `verify_installation` to check
+    /// every file.
+    pub fn needs_download(&self, manifest: &Manifest, base_path: &PathBuf) -> bool {
+        let paths = &self.options.paths();
+        let Some(asset_index) = read_json(&asset_index_path(manifest, base_path, paths)) else {
+            return true;
+        };
+        let client = DownloadData {
+            output_path: version_jar_path(manifest, base_path, None, paths)
+                .to_string_lossy()
+                .to_string(),
+            ..DownloadData::from(manifest.downloads.client.clone())
+        };
+        let libraries = library_downloads(&manifest.libraries, base_path, paths);
+        // The asset index comes first
+        let assets = asset_downloads(manifest, base_path, &asset_index, paths);
+
+        std::iter::once(client)
+            .chain(libraries)
+            .chain(assets.into_iter().take(1 + ASSET_SAMPLE))
+            .any(|download| {
+                let path = PathBuf::from(download.output_path());
+                match verify_file(download.sha1(), path.clone()) {
+                    VerifyStatus::Ok => false,
+                    VerifyStatus::Failed => true,
+                    // Without a hash, the file only has to exist
+                    VerifyStatus::NotVerified => !path.is_file(),
+                }
+            })
+    }
+
+    /// Downloads again the files of an installed version that are missing,
+    /// corrupt or can't be verified, and only those. The natives are
+    /// extracted again either way.
+    ///
+    /// Returns the results of the repaired files, empty if nothing had to be
+    /// repaired.
+    pub fn repair_installation(
+        &self,
+        manifest: &Manifest,
+        base_path: &PathBuf,
+        progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+        let mut downloads: Vec<DownloadData> = Vec::new();
+        for (download, status) in installation_status(manifest, base_path, &self.options.paths()) {
+            match status {
+                VerifyStatus::Ok => continue,
+                // A corrupt file would otherwise be resumed instead of replaced
+                VerifyStatus::Failed => match std::fs::remove_file(download.output_path()) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                },
+                VerifyStatus::NotVerified => {}
+            }
+            downloads.push(DownloadData {
+                url: self.options.mirror.rewrite(download.url()),
+                ..download
+            });
+        }
+
+        let results = if downloads.is_empty() {
+            Vec::new()
+        } else {
+            self.options
+                .downloader_service(base_path.clone())
+                .with_downloads(downloads)
+                .run(progress)?
+        };
+
+        // The natives directory may be gone even with every jar intact
+        let version_path = version_jar_path(manifest, base_path, None, &self.options.paths());
+        self.options
+            .extract_natives(manifest, base_path, &version_path)?;
+        Ok(results)
+    }
+
+    /// Deletes the directory of the client jar of the version, e.g.
+    /// `base_path/versions/{version_id}`. When the directory isn't named
+    /// after the version, only the jar, its `{id}.json` and its natives are.
+    ///
+    /// With `prune`, the libraries, assets and asset index of the version
+    /// are deleted too, unless another version `installed_versions` lists
+    /// still uses them. That needs the `{id}.json` manifests
+    /// `download_by_manifest` saves. Files in the directories set by
+    /// `with_libraries_path` and `with_assets_path` are always kept, other
+    /// installations may share them.
+    pub fn remove_version(
+        &self,
+        base_path: &PathBuf,
+        version_id: &str,
+        prune: bool,
+    ) -> Result<RemovedVersion, ClientDownloaderError> {
+        // Only a plain directory name, never `..` or an absolute path
+        let mut components = Path::new(version_id).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) {
+            return Err(ClientDownloaderError::InvalidPath(PathBuf::from(
+                version_id,
+            )));
+        }
+
+        let paths = &self.options.paths();
+        let version_jar = paths.client_jar(base_path, version_id);
+        let version_dir = parent_dir(&version_jar)?;
+        if !version_dir.is_dir() {
+            return Err(ClientDownloaderError::NoSuchVersion);
+        }
+
+        let mut removed = RemovedVersion::default();
+        let manifest = installed_manifest(base_path, version_id, paths);
+
+        // A directory shared with other versions only loses the files of
+        // this one
+        let version_files = match version_dir.file_name() {
+            Some(name) if name == version_id => vec![version_dir.to_path_buf()],
+            _ => vec![
+                version_jar.clone(),
+                version_jar.with_extension("json"),
+                version_dir.join("natives"),
+            ],
+        };
+        for path in version_files {
+            let Ok(metadata) = path.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                removed.bytes += dir_size(&path);
+                std::fs::remove_dir_all(&path)?;
+            } else {
+                removed.bytes += metadata.len();
+                std::fs::remove_file(&path)?;
+            }
+            removed.paths.push(path);
+        }
+
+        let Some(manifest) = manifest.filter(|_| prune) else {
+            return Ok(removed);
+        };
+
+        // Everything the remaining versions reference has to stay
+        let referenced: HashSet<PathBuf> = Self::installed_versions(base_path)
+            .iter()
+            .filter_map(|id| installed_manifest(base_path, id, paths))
+            .flat_map(|manifest| files_of(&manifest, base_path, paths))
+            .collect();
+        let shared_roots: Vec<&PathBuf> = [&self.options.libraries_path, &self.options.assets_path]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        // The asset index goes last, the assets are listed from it
+        let index_path = asset_index_path(&manifest, base_path, paths);
+        let mut files: Vec<PathBuf> = files_of(&manifest, base_path, paths)
+            .into_iter()
+            .filter(|file| *file != index_path)
+            .collect();
+        files.push(index_path);
+
+        for file in files {
+            if referenced.contains(&file) || shared_roots.iter().any(|root| file.starts_with(root))
+            {
+                continue;
+            }
+            let Ok(metadata) = file.metadata() else {
+                continue;
+            };
+
+            std::fs::remove_file(&file)?;
+            removed.bytes += metadata.len();
+            removed.paths.push(file);
+        }
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::json;
+
+    use super::{ClientDownloader, VerifyStatus};
+    use crate::error::ClientDownloaderError;
+    use crate::manifest::Manifest;
+
+    fn downloader() -> ClientDownloader {
+        ClientDownloader::from_manifest(
+            serde_json::from_value(
+                json!({"latest": {"release": "", "snapshot": ""}, "versions": []}),
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn install(base_path: &Path, id: &str, libraries: &[&str]) -> Manifest {
+        let version_dir = base_path.join("versions").join(id);
+        std::fs::create_dir_all(&version_dir).unwrap();
+        std::fs::write(version_dir.join(format!("{id}.jar")), "jar").unwrap();
+
+        let file = |path: &str| json!({"path": path, "sha1": "", "size": 1, "url": ""});
+        let libraries: Vec<_> = libraries
+            .iter()
+            .map(|path| json!({"downloads": {"artifact": file(path)}, "name": path}))
+            .collect();
+        let manifest = json!({
+            "arguments": {},
+            "assetIndex": {"id": id, "sha1": "", "size": 1, "totalSize": 1, "url": ""},
+            "assets": id,
+            "complianceLevel": 1,
+            "downloads": {"client": file(""), "server": file("")},
+            "id": id,
+            "javaVersion": {"component": "", "majorVersion": 17},
+            "libraries": libraries,
+            "logging": {"client": {"argument": "", "file": {"id": "", "sha1": "", "size": 1, "url": ""}, "type": ""}},
+            "mainClass": "",
+            "minimumLauncherVersion": 21,
+            "releaseTime": "",
+            "time": "",
+            "type": "release"
+        });
+        std::fs::write(version_dir.join(format!("{id}.json")), manifest.to_string()).unwrap();
+
+        for library in libraries.iter() {
+            let path = base_path
+                .join("libraries")
+                .join(library["name"].as_str().unwrap());
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "lib").unwrap();
+        }
+
+        serde_json::from_value(manifest).unwrap()
+    }
+
+    #[test]
+    fn shared_libraries_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().to_path_buf();
+        let _ = install(&base_path, "1.20.1", &["shared.jar", "old.jar"]);
+        let _ = install(&base_path, "1.20.2", &["shared.jar"]);
+
+        let removed = downloader()
+            .remove_version(&base_path, "1.20.1", true)
+            .unwrap();
+
+        assert!(!base_path.join("versions/1.20.1").exists());
+        assert!(!base_path.join("libraries/old.jar").exists());
+        assert!(base_path.join("libraries/shared.jar").exists());
+        assert_eq!(removed.paths.len(), 2);
+        assert!(removed.bytes > 0);
+    }
+
+    #[test]
+    fn overridden_libraries_are_not_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().to_path_buf();
+        let _ = install(&base_path, "1.20.1", &["old.jar"]);
+
+        let mut downloader = downloader();
+        downloader.with_libraries_path(base_path.join("libraries"));
+        let removed = downloader
+            .remove_version(&base_path, "1.20.1", true)
+            .unwrap();
+
+        assert!(!base_path.join("versions/1.20.1").exists());
+        assert!(base_path.join("libraries/old.jar").exists());
+        assert_eq!(removed.paths.len(), 1);
+    }
+
+    #[test]
+    fn only_version_directories_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().join("minecraft");
+        let _ = install(&base_path, "1.20.1", &[]);
+
+        for version_id in ["..", "../..", "1.20.1/..", "", "/tmp"] {
+            assert!(matches!(
+                downloader().remove_version(&base_path, version_id, false),
+                Err(ClientDownloaderError::InvalidPath(_))
+            ));
+        }
+        assert!(base_path.join("versions/1.20.1").exists());
+    }
+
+    #[test]
+    fn missing_files_fail_verification() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().to_path_buf();
+        let mut manifest = install(&base_path, "1.20.1", &["lib.jar"]);
+        manifest.downloads.client.sha1 = "0".repeat(40);
+        if let Some(artifact) = &mut manifest.libraries[0].downloads.artifact {
+            artifact.url = "https://example.com/lib.jar".to_string();
+        }
+
+        let statuses = downloader().verify_installation(&manifest, &base_path);
+        let status = |path: &Path| {
+            statuses
+                .iter()
+                .find(|(p, _)| p == path)
+                .map(|(_, status)| status.clone())
+        };
+
+        assert_eq!(
+            status(&base_path.join("libraries/lib.jar")),
+            Some(VerifyStatus::NotVerified)
+        );
+        assert_eq!(
+            status(&base_path.join("versions/1.20.1/1.20.1.jar")),
+            Some(VerifyStatus::Failed)
+        );
+    }
+
+    #[test]
+    fn missing_files_need_a_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().to_path_buf();
+        let mut manifest = install(&base_path, "1.20.1", &["lib.jar"]);
+        if let Some(artifact) = &mut manifest.libraries[0].downloads.artifact {
+            artifact.url = "https://example.com/lib.jar".to_string();
+        }
+        assert!(downloader().needs_download(&manifest, &base_path));
+
+        let indexes = base_path.join("assets/indexes");
+        std::fs::create_dir_all(&indexes).unwrap();
+        std::fs::write(indexes.join("1.20.1.json"), r#"{"objects": {}}"#).unwrap();
+        assert!(!downloader().needs_download(&manifest, &base_path));
+
+        // The libraries are looked up where the downloader stores them
+        let mut shared = downloader();
+        shared.with_libraries_path(dir.path().join("shared"));
+        assert!(shared.needs_download(&manifest, &base_path));
+
+        std::fs::remove_file(base_path.join("libraries/lib.jar")).unwrap();
+        assert!(downloader().needs_download(&manifest, &base_path));
+    }
+}
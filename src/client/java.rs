@@ -0,0 +1,332 @@
+use flate2::read::GzDecoder;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use zip::ZipArchive;
+
+use std::fs::{create_dir_all, File};
+use std::path::{Component, Path, PathBuf};
+
+use super::{verify_file_with, HashAlgorithm, VerifyStatus};
+use crate::error::ClientDownloaderError;
+
+/// Where a JDK is downloaded from.
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
+pub enum JavaVendor {
+    #[default]
+    Oracle,
+    Temurin,
+    Zulu,
+}
+
+#[derive(Deserialize)]
+struct ZuluPackage {
+    download_url: String,
+}
+
+#[derive(Deserialize)]
+struct TemurinRelease {
+    binary: TemurinBinary,
+}
+
+#[derive(Deserialize)]
+struct TemurinBinary {
+    package: TemurinPackage,
+}
+
+#[derive(Deserialize)]
+struct TemurinPackage {
+    link: String,
+    checksum: String,
+}
+
+/// A JDK archive to download, with its SHA-256 when the vendor publishes it.
+pub(super) struct JavaArchive {
+    pub(super) url: String,
+    pub(super) sha256: Option<String>,
+}
+
+impl JavaArchive {
+    fn unverified(url: String) -> Self {
+        Self { url, sha256: None }
+    }
+
+    /// Checks the archive downloaded to `path` against its checksum, or
+    /// reads it through when there is none.
+    pub(super) fn check(&self, path: &Path) -> Result<(), ClientDownloaderError> {
+        let Some(sha256) = &self.sha256 else {
+            return check_java_archive(path);
+        };
+        match verify_file_with(HashAlgorithm::Sha256, sha256, path.to_path_buf()) {
+            VerifyStatus::Failed => Err(ClientDownloaderError::JavaChecksumMismatch(
+                path.to_path_buf(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The archive extension of the JDK builds for the current platform.
+pub(super) fn java_archive_extension() -> &'static str {
+    match std::env::consts::OS {
+        "macos" | "linux" => ".tar.gz",
+        _ => ".zip",
+    }
+}
+
+fn vendor_os(vendor: JavaVendor) -> &'static str {
+    match (vendor, std::env::consts::OS) {
+        (JavaVendor::Temurin, "macos") => "mac",
+        (_, os) => os,
+    }
+}
+
+fn vendor_arch(vendor: JavaVendor) -> &'static str {
+    match (vendor, std::env::consts::ARCH) {
+        (_, "x86_64") => "x64",
+        (JavaVendor::Temurin, "x86") => "x32",
+        (_, arch) => arch,
+    }
+}
+
+/// The vendor that publishes a build of `version`: Oracle only serves the
+/// newest release of majors since 17, older ones come from Temurin.
+fn release_vendor(vendor: JavaVendor, version: &str) -> JavaVendor {
+    match (vendor, version.parse::<u8>()) {
+        (JavaVendor::Oracle, Ok(major)) if major < 17 => JavaVendor::Temurin,
+        _ => vendor,
+    }
+}
+
+/// Resolves the JDK archive of `version` for the current platform.
+pub(super) fn java_archive(
+    client: &Client,
+    vendor: JavaVendor,
+    version: &str,
+) -> Result<JavaArchive, ClientDownloaderError> {
+    let vendor = release_vendor(vendor, version);
+    let os = vendor_os(vendor);
+    let arch = vendor_arch(vendor);
+    let ext = java_archive_extension();
+
+    match vendor {
+        // A bare major version resolves to the newest release of it
+        JavaVendor::Oracle if !version.contains('.') => Ok(JavaArchive::unverified(format!(
+            "https://download.oracle.com/java/{version}/latest/jdk-{version}_{os}-{arch}_bin{ext}"
+        ))),
+        JavaVendor::Oracle => Ok(JavaArchive::unverified(format!(
+            "https://download.oracle.com/java/{version}/archive/jdk-{version}_{os}-{arch}_bin{ext}"
+        ))),
+        JavaVendor::Temurin => {
+            let response = client
+                .get(format!(
+                    "https://api.adoptium.net/v3/assets/latest/{version}/hotspot"
+                ))
+                .query(&[
+                    ("os", os),
+                    ("architecture", arch),
+                    ("image_type", "jdk"),
+                    ("vendor", "eclipse"),
+                ])
+                .send()?
+                .error_for_status()?;
+
+            let releases: Vec<TemurinRelease> = serde_json::from_reader(response)?;
+            releases
+                .into_iter()
+                .next()
+                .map(|release| JavaArchive {
+                    url: release.binary.package.link,
+                    sha256: Some(release.binary.package.checksum),
+                })
+                .ok_or(ClientDownloaderError::NoSuchVersion)
+        }
+        JavaVendor::Zulu => {
+            let response = client
+                .get("https://api.azul.com/metadata/v1/zulu/packages/")
+                .query(&[
+                    ("java_version", version),
+                    ("os", os),
+                    ("arch", arch),
+                    ("archive_type", ext.trim_start_matches('.')),
+                    ("java_package_type", "jdk"),
+                    ("javafx_bundled", "false"),
+                    ("release_status", "ga"),
+                    ("latest", "true"),
+                ])
+                .send()?
+                .error_for_status()?;
+
+            let packages: Vec<ZuluPackage> = serde_json::from_reader(response)?;
+            packages
+                .into_iter()
+                .next()
+                .map(|p| JavaArchive::unverified(p.download_url))
+                .ok_or(ClientDownloaderError::NoSuchVersion)
+        }
+    }
+}
+
+/// The path of the `java` binary of a JDK installed in `java_home`.
+pub fn java_executable(java_home: &Path) -> PathBuf {
+    let binary = if cfg!(windows) { "java.exe" } else { "java" };
+    java_home.join("bin").join(binary)
+}
+
+/// Maps a path inside a JDK archive to its path inside the java home.
+///
+/// The archives wrap everything in a `jdk-{version}` directory, and the macOS
+/// builds additionally nest the java home in `Contents/Home`.
+fn java_home_relative(path: &Path) -> Option<PathBuf> {
+    let inner: PathBuf = path
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .skip(1)
+        .collect();
+
+    if inner.starts_with("Contents") {
+        return inner
+            .strip_prefix("Contents/Home")
+            .ok()
+            .map(Path::to_path_buf);
+    }
+    Some(inner)
+}
+
+/// Reads a downloaded JDK archive through, for the archives without a
+/// checksum to at least catch truncated or corrupt downloads.
+fn check_java_archive(archive_path: &Path) -> Result<(), ClientDownloaderError> {
+    let invalid = || ClientDownloaderError::InvalidJavaArchive(archive_path.to_path_buf());
+    let archive = File::open(archive_path)?;
+
+    if archive_path.to_string_lossy().ends_with(".zip") {
+        let mut archive = ZipArchive::new(archive).map_err(|_| invalid())?;
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).map_err(|_| invalid())?;
+            std::io::copy(&mut entry, &mut std::io::sink()).map_err(|_| invalid())?;
+        }
+    } else {
+        let mut archive = tar::Archive::new(GzDecoder::new(archive));
+        for entry in archive.entries().map_err(|_| invalid())? {
+            std::io::copy(&mut entry.map_err(|_| invalid())?, &mut std::io::sink())
+                .map_err(|_| invalid())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Unpacks a downloaded JDK archive into `java_home`.
+pub(super) fn extract_java_archive(
+    archive_path: &Path,
+    java_home: &Path,
+) -> Result<(), ClientDownloaderError> {
+    create_dir_all(java_home)?;
+    let archive = File::open(archive_path)?;
+
+    if archive_path.to_string_lossy().ends_with(".zip") {
+        let mut archive = ZipArchive::new(archive)?;
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let Some(relative) = entry.enclosed_name().and_then(java_home_relative) else {
+                continue;
+            };
+            let output_path = java_home.join(relative);
+
+            if entry.is_dir() {
+                create_dir_all(&output_path)?;
+                continue;
+            }
+            if let Some(parent) = output_path.parent() {
+                create_dir_all(parent)?;
+            }
+            std::io::copy(&mut entry, &mut File::create(&output_path)?)?;
+
+            #[cfg(unix)]
+            if let Some(mode) = entry.unix_mode() {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&output_path, std::fs::Permissions::from_mode(mode))?;
+            }
+        }
+    } else {
+        let mut archive = tar::Archive::new(GzDecoder::new(archive));
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let Some(relative) = java_home_relative(&entry.path()?) else {
+                continue;
+            };
+            if relative.as_os_str().is_empty() {
+                continue;
+            }
+            let output_path = java_home.join(relative);
+
+            if let Some(parent) = output_path.parent() {
+                create_dir_all(parent)?;
+            }
+            entry.unpack(&output_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{check_java_archive, java_home_relative, release_vendor, JavaVendor};
+
+    #[test]
+    fn java_home_layout_is_normalized() {
+        assert_eq!(
+            java_home_relative(Path::new("jdk-17.0.2/bin/java")),
+            Some(PathBuf::from("bin/java"))
+        );
+        assert_eq!(
+            java_home_relative(Path::new("jdk-17.0.2+8/Contents/Home/bin/java")),
+            Some(PathBuf::from("bin/java"))
+        );
+        assert_eq!(
+            java_home_relative(Path::new("jdk-17.0.2+8/Contents/Info.plist")),
+            None
+        );
+    }
+
+    #[test]
+    fn majors_without_an_oracle_build_use_temurin() {
+        for major in ["8", "11", "16"] {
+            assert_eq!(
+                release_vendor(JavaVendor::Oracle, major),
+                JavaVendor::Temurin
+            );
+        }
+        assert_eq!(release_vendor(JavaVendor::Oracle, "21"), JavaVendor::Oracle);
+        assert_eq!(
+            release_vendor(JavaVendor::Oracle, "17.0.2"),
+            JavaVendor::Oracle
+        );
+        assert_eq!(release_vendor(JavaVendor::Zulu, "8"), JavaVendor::Zulu);
+    }
+
+    #[test]
+    fn corrupt_archives_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("jdk-17.tar.gz");
+
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            std::fs::File::create(&archive_path).unwrap(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "jdk-17/release", &b"17.0"[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+        assert!(check_java_archive(&archive_path).is_ok());
+
+        let bytes = std::fs::read(&archive_path).unwrap();
+        std::fs::write(&archive_path, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(check_java_archive(&archive_path).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+/// The hosts of the Mojang metadata, client and server jars.
+const MOJANG_HOSTS: [&str; 4] = [
+    "launchermeta.mojang.com",
+    "launcher.mojang.com",
+    "piston-meta.mojang.com",
+    "piston-data.mojang.com",
+];
+
+/// Base urls to use instead of the official hosts, e.g. for BMCLAPI:
+/// `meta` is `https://bmclapi2.bangbang93.com`, `assets` is
+/// `https://bmclapi2.bangbang93.com/assets` and `libraries` is
+/// `https://bmclapi2.bangbang93.com/maven`.
+///
+/// The default keeps the official hosts.
+#[derive(Clone, Debug, Default)]
+pub struct MirrorConfig {
+    /// Replaces the Mojang hosts of the manifests and of the game jars.
+    pub meta: Option<String>,
+    /// Replaces `resources.download.minecraft.net`.
+    pub assets: Option<String>,
+    /// Replaces `libraries.minecraft.net`.
+    pub libraries: Option<String>,
+    /// Replaces `meta.fabricmc.net`.
+    pub fabric_meta: Option<String>,
+    /// Replaces `meta.quiltmc.org`.
+    pub quilt_meta: Option<String>,
+    /// Replaces `maven.minecraftforge.net`, for the Forge installers and
+    /// libraries.
+    pub forge_maven: Option<String>,
+    /// Replaces `maven.neoforged.net`, for the NeoForge installers and
+    /// libraries.
+    pub neoforge_maven: Option<String>,
+}
+
+impl MirrorConfig {
+    /// `url` with its host replaced by the mirror of that host, unchanged
+    /// when there is none.
+    pub fn rewrite(&self, url: &str) -> String {
+        let mirrors = MOJANG_HOSTS.iter().map(|host| (*host, &self.meta)).chain([
+            ("resources.download.minecraft.net", &self.assets),
+            ("libraries.minecraft.net", &self.libraries),
+            ("meta.fabricmc.net", &self.fabric_meta),
+            ("meta.quiltmc.org", &self.quilt_meta),
+            ("maven.minecraftforge.net", &self.forge_maven),
+            ("maven.neoforged.net", &self.neoforge_maven),
+        ]);
+
+        for (host, mirror) in mirrors {
+            let Some(mirror) = mirror else {
+                continue;
+            };
+            let path = ["https://", "http://"]
+                .iter()
+                .find_map(|scheme| url.strip_prefix(scheme)?.strip_prefix(host))
+                .filter(|path| path.is_empty() || path.starts_with('/'));
+
+            if let Some(path) = path {
+                return format!("{}{path}", mirror.trim_end_matches('/'));
+            }
+        }
+
+        url.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MirrorConfig;
+
+    #[test]
+    fn official_hosts_are_rewritten() {
+        let mirror = MirrorConfig {
+            meta: Some("https://bmclapi2.bangbang93.com/".to_string()),
+            assets: Some("https://bmclapi2.bangbang93.com/assets".to_string()),
+            forge_maven: Some("https://bmclapi2.bangbang93.com/maven".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            mirror.rewrite("https://piston-meta.mojang.com/v1/packages/1.20.1.json"),
+            "https://bmclapi2.bangbang93.com/v1/packages/1.20.1.json"
+        );
+        assert_eq!(
+            mirror.rewrite("https://resources.download.minecraft.net/bd/bdf48ef6"),
+            "https://bmclapi2.bangbang93.com/assets/bd/bdf48ef6"
+        );
+        assert_eq!(
+            mirror.rewrite("https://maven.minecraftforge.net/net/minecraftforge/forge/a.jar"),
+            "https://bmclapi2.bangbang93.com/maven/net/minecraftforge/forge/a.jar"
+        );
+        assert_eq!(
+            mirror.rewrite("https://libraries.minecraft.net/a/a.jar"),
+            "https://libraries.minecraft.net/a/a.jar"
+        );
+        assert_eq!(
+            mirror.rewrite("https://piston-meta.mojang.com.example.com/a.json"),
+            "https://piston-meta.mojang.com.example.com/a.json"
+        );
+    }
+}
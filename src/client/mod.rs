@@ -1,5 +1,13 @@
+#[cfg(feature = "async")]
+mod async_client_downloader;
 mod client_downloader;
 mod downloader;
+mod installation;
+mod java;
+mod mirror;
+mod natives;
+mod paths;
+mod processors;
 mod verify;
 
 use std::{
@@ -7,37 +15,177 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+#[cfg(feature = "async")]
+pub use async_client_downloader::*;
 pub use client_downloader::*;
 pub use downloader::*;
+pub use installation::RemovedVersion;
+pub use java::{java_executable, JavaVendor};
+pub use mirror::MirrorConfig;
+pub use natives::*;
+pub use paths::{PathStrategy, VanillaPaths};
+pub use processors::run_processors;
 pub use verify::*;
 
 use crate::{
     error::{ClientDownloaderError, DownloadError},
-    manifest::Manifest,
+    manifest::{ForgeInstallProfile, Manifest, ManifestLibrary},
 };
 
+/// What happened to one file: its `DownloadOutput`, or the `DownloadError`
+/// that holds it when the file failed.
 pub type DownloadResult = Result<DownloadOutput, DownloadError>;
 
+/// The outcome of one file of a download.
 #[derive(Default, Clone)]
 pub struct DownloadOutput {
     pub status: u16,
     pub file_name: String,
+    pub url: String,
     pub file_path: PathBuf,
     pub verified: VerifyStatus,
+    /// How many times the file was requested.
+    pub attempts: u16,
+    /// The file was already on disk and valid, nothing was downloaded.
+    pub skipped: bool,
+    /// How many bytes were transferred, over all the attempts.
+    pub bytes: u64,
+    /// The file was opened for writing by this download.
+    pub written: bool,
+}
+
+/// What `download_version` installed: the manifest after the loader was
+/// merged into it, and the downloaded files.
+#[derive(Debug)]
+pub struct InstalledVersion {
+    pub manifest: Manifest,
+    pub results: Vec<DownloadResult>,
+    /// The installer of a Forge or NeoForge version, to finish the install
+    /// with `run_processors`. `None` for the other launchers.
+    pub forge_installer: Option<ForgeInstaller>,
+}
+
+/// The Forge or NeoForge installer a version was set up from.
+#[derive(Clone, Debug)]
+pub struct ForgeInstaller {
+    /// The installer jar, kept with the libraries.
+    pub path: PathBuf,
+    pub profile: ForgeInstallProfile,
+    /// The libraries only the processors use, downloaded with the version
+    /// but kept off its classpath.
+    pub libraries: Vec<ManifestLibrary>,
 }
 
 /// A Progress reporter to use for the `Download`
 pub type Progress = Arc<Mutex<dyn Reporter>>;
 
+/// The metadata fetched before the files of a version can be downloaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metadata {
+    VersionManifest,
+    /// The manifest of the Fabric, Quilt, Forge or NeoForge loader.
+    LoaderManifest,
+    AssetIndex,
+}
+
+/// What happened to a single file of a download.
+#[derive(Clone, Debug)]
+pub enum ProgressEvent {
+    /// The metadata is being fetched and parsed, no file progress is
+    /// reported until it is.
+    Resolving {
+        metadata: Metadata,
+    },
+    Resolved {
+        metadata: Metadata,
+    },
+    FileStarted {
+        file_name: String,
+        total: u64,
+    },
+    /// `current` is how much of the file is on disk so far.
+    BytesDownloaded {
+        file_name: String,
+        current: u64,
+        total: u64,
+    },
+    FileCompleted {
+        file_name: String,
+        total: u64,
+    },
+    FileFailed {
+        file_name: String,
+    },
+    /// The download was cancelled before the file was finished.
+    FileCancelled {
+        file_name: String,
+    },
+}
+
 /// An interface for `ProgressReporter`s
 pub trait Reporter: Send + Sync {
     fn setup(&mut self, _max_progress: u64) {}
+    /// How many files are going to be downloaded, right after `setup`
+    fn setup_files(&mut self, _files: u64) {}
     /// Report progress
     fn progress(&mut self, _current: u64) {}
+    /// Report what happened to a single file, next to the overall `progress`
+    fn event(&mut self, _event: ProgressEvent) {}
     /// Finish up after progress reporting is done
     fn done(&mut self) {}
 }
 
+/// A `Reporter` that keeps count of the progress, to read it from another
+/// thread, e.g. as a percentage.
+#[derive(Clone, Debug, Default)]
+pub struct ProgressState {
+    pub total_bytes: u64,
+    pub downloaded_bytes: u64,
+    pub total_files: u64,
+    /// The files completed, failed or cancelled so far.
+    pub finished_files: u64,
+}
+
+impl ProgressState {
+    /// The progress from 0 to 100, by bytes, or by files when the total
+    /// size isn't known.
+    pub fn percentage(&self) -> f64 {
+        let (done, total) = match self.total_bytes {
+            0 => (self.finished_files, self.total_files),
+            total => (self.downloaded_bytes, total),
+        };
+        if total == 0 {
+            return 0.0;
+        }
+
+        (done as f64 / total as f64 * 100.0).min(100.0)
+    }
+}
+
+impl Reporter for ProgressState {
+    fn setup(&mut self, max_progress: u64) {
+        self.total_bytes = max_progress;
+    }
+
+    fn setup_files(&mut self, files: u64) {
+        self.total_files = files;
+    }
+
+    fn progress(&mut self, current: u64) {
+        self.downloaded_bytes += current;
+    }
+
+    fn event(&mut self, event: ProgressEvent) {
+        if let ProgressEvent::FileCompleted { .. }
+        | ProgressEvent::FileFailed { .. }
+        | ProgressEvent::FileCancelled { .. } = event
+        {
+            self.finished_files += 1;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub trait DownloadVersion {
     fn download_version(
         &self,
@@ -49,7 +197,7 @@ pub trait DownloadVersion {
         _launcher: Option<Launcher>,
         _launcher_id: Option<&str>,
         _progress: Option<Progress>,
-    ) -> Result<Vec<DownloadResult>, ClientDownloaderError>;
+    ) -> Result<InstalledVersion, ClientDownloaderError>;
 
     fn setup_fabric(
         &self,
@@ -58,7 +206,38 @@ pub trait DownloadVersion {
         _base_manifest: &mut Manifest,
     ) -> Result<Manifest, ClientDownloaderError>;
 
-    fn create_profiles_json(&self, _game_path: &PathBuf) -> Result<(), ClientDownloaderError>;
+    fn setup_quilt(
+        &self,
+        _version_id: &str,
+        _launcher_id: &str,
+        _base_manifest: &mut Manifest,
+    ) -> Result<Manifest, ClientDownloaderError>;
+
+    /// Also saves the installer jar with the libraries of `base_path`.
+    fn setup_forge(
+        &self,
+        _version_id: &str,
+        _launcher_id: &str,
+        _base_manifest: &mut Manifest,
+        _base_path: &PathBuf,
+    ) -> Result<(Manifest, ForgeInstaller), ClientDownloaderError>;
+
+    /// Also saves the installer jar with the libraries of `base_path`.
+    fn setup_neoforge(
+        &self,
+        _version_id: &str,
+        _launcher_id: &str,
+        _base_manifest: &mut Manifest,
+        _base_path: &PathBuf,
+    ) -> Result<(Manifest, ForgeInstaller), ClientDownloaderError>;
+
+    /// Adds a profile for the version to the `launcher_profiles.json` of
+    /// the game directory, keeping the profiles already in it.
+    fn create_profiles_json(
+        &self,
+        _game_path: &PathBuf,
+        _version_id: &str,
+    ) -> Result<(), ClientDownloaderError>;
 
     fn download_by_manifest(
         &self,
@@ -72,16 +251,25 @@ pub trait DownloadVersion {
 
 pub trait DownloadJava {
     fn check_version(&self, _root_path: &str, _expected_version: &str) -> bool;
-    fn download_java(&self, _root_path: &str, _version: &str, _progress: Option<Progress>);
+    fn download_java(
+        &self,
+        _root_path: &str,
+        _version: &str,
+        _vendor: Option<JavaVendor>,
+        _progress: Option<Progress>,
+    ) -> Result<PathBuf, ClientDownloaderError>;
 }
 
 fn download_result_to_fmt(
     f: &mut std::fmt::Formatter<'_>,
     summary: &DownloadOutput,
 ) -> std::fmt::Result {
+    if summary.skipped {
+        return writeln!(f, "{}: Skipped", summary.file_name);
+    }
     writeln!(
         f,
-        "{}: (verification: {}) Status: {}",
+        "{}: (verification: {}) Status: {} Attempts: {} Bytes: {}",
         summary.file_name,
         match summary.verified {
             VerifyStatus::NotVerified => "unverified",
@@ -89,6 +277,8 @@ fn download_result_to_fmt(
             VerifyStatus::Ok => "Ok",
         },
         summary.status,
+        summary.attempts,
+        summary.bytes,
     )?;
     Ok(())
 }
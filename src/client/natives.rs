@@ -0,0 +1,63 @@
+use std::fs::{create_dir_all, File};
+use std::path::{Path, PathBuf};
+
+use zip::ZipArchive;
+
+use super::PathStrategy;
+use crate::error::ClientDownloaderError;
+use crate::manifest::{native_library_file, Manifest};
+
+/// Extracts the native libraries of the current platform, downloaded to
+/// where `paths` puts them under `base_path`, into `natives_dir`.
+///
+/// `META-INF` and the `extract.exclude` entries of the library are skipped,
+/// and so are jars that aren't downloaded. Returns the paths of the
+/// extracted files.
+pub fn extract_natives(
+    manifest: &Manifest,
+    base_path: &Path,
+    paths: &dyn PathStrategy,
+    natives_dir: &PathBuf,
+) -> Result<Vec<PathBuf>, ClientDownloaderError> {
+    let mut extracted = Vec::new();
+    create_dir_all(natives_dir)?;
+
+    for library in &manifest.libraries {
+        let Some(file) = native_library_file(library) else {
+            continue;
+        };
+        let Some(path) = file.path else { continue };
+
+        let mut exclude = vec!["META-INF/".to_string()];
+        if let Some(extract) = &library.extract {
+            exclude.extend(extract.exclude.clone());
+        }
+
+        // A failed download is in the results of the download already
+        let jar = match File::open(paths.library(base_path, &path)) {
+            Ok(jar) => jar,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let mut archive = ZipArchive::new(jar)?;
+
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            if entry.is_dir() || exclude.iter().any(|e| entry.name().starts_with(e.as_str())) {
+                continue;
+            }
+            let Some(name) = entry.enclosed_name().map(|n| n.to_path_buf()) else {
+                continue;
+            };
+
+            let output_path = natives_dir.join(name);
+            if let Some(parent) = output_path.parent() {
+                create_dir_all(parent)?;
+            }
+            std::io::copy(&mut entry, &mut File::create(&output_path)?)?;
+            extracted.push(output_path);
+        }
+    }
+
+    Ok(extracted)
+}
@@ -0,0 +1,149 @@
+use std::path::{Path, PathBuf};
+
+/// Where the files of a version are stored under the `base_path` of a
+/// download.
+///
+/// Every method defaults to the layout of the vanilla launcher, override
+/// the ones that differ, e.g. for per-instance layouts.
+pub trait PathStrategy: Send + Sync {
+    /// The client jar of the version `version_id`.
+    fn client_jar(&self, base_path: &Path, version_id: &str) -> PathBuf {
+        base_path
+            .join("versions")
+            .join(version_id)
+            .join(format!("{version_id}.jar"))
+    }
+
+    /// The assets directory, holding the asset index, the objects and the
+    /// logging configurations.
+    fn assets(&self, base_path: &Path) -> PathBuf {
+        base_path.join("assets")
+    }
+
+    /// The asset object with the sha1 `hash`.
+    fn asset_object(&self, base_path: &Path, hash: &str) -> PathBuf {
+        self.assets(base_path)
+            .join("objects")
+            .join(&hash[..2.min(hash.len())])
+            .join(hash)
+    }
+
+    /// The library at `path` in its maven repository.
+    fn library(&self, base_path: &Path, path: &str) -> PathBuf {
+        join_maven_path(&base_path.join("libraries"), path)
+    }
+}
+
+/// Joins the `/` separated path of a maven repository to `base` component by
+/// component, so the result only has the separators of the platform.
+pub(super) fn join_maven_path(base: &Path, path: &str) -> PathBuf {
+    path.split('/')
+        .filter(|component| !component.is_empty())
+        .fold(base.to_path_buf(), |joined, component| {
+            joined.join(component)
+        })
+}
+
+/// The layout of the vanilla launcher: `versions/{id}/{id}.jar`,
+/// `assets/objects/{hash[..2]}/{hash}` and `libraries/{path}`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VanillaPaths;
+
+impl PathStrategy for VanillaPaths {}
+
+/// A path strategy with the directories set by the `with_*_path` methods of
+/// the downloader taking precedence.
+pub(super) struct PathOverrides<'a> {
+    pub(super) paths: &'a dyn PathStrategy,
+    pub(super) assets: Option<&'a Path>,
+    pub(super) libraries: Option<&'a Path>,
+}
+
+impl PathStrategy for PathOverrides<'_> {
+    fn client_jar(&self, base_path: &Path, version_id: &str) -> PathBuf {
+        self.paths.client_jar(base_path, version_id)
+    }
+
+    fn assets(&self, base_path: &Path) -> PathBuf {
+        match self.assets {
+            Some(assets) => assets.to_path_buf(),
+            None => self.paths.assets(base_path),
+        }
+    }
+
+    fn asset_object(&self, base_path: &Path, hash: &str) -> PathBuf {
+        match self.assets {
+            Some(assets) => assets
+                .join("objects")
+                .join(&hash[..2.min(hash.len())])
+                .join(hash),
+            None => self.paths.asset_object(base_path, hash),
+        }
+    }
+
+    fn library(&self, base_path: &Path, path: &str) -> PathBuf {
+        match self.libraries {
+            Some(libraries) => join_maven_path(libraries, path),
+            None => self.paths.library(base_path, path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{join_maven_path, PathStrategy, VanillaPaths};
+
+    struct SharedLibraries;
+
+    impl PathStrategy for SharedLibraries {
+        fn library(&self, _base_path: &Path, path: &str) -> PathBuf {
+            Path::new("/shared/libraries").join(path)
+        }
+    }
+
+    #[test]
+    fn overridden_paths_keep_the_vanilla_defaults() {
+        let base_path = Path::new("/tmp/.minecraft");
+
+        assert_eq!(
+            SharedLibraries.library(base_path, "a/a.jar"),
+            PathBuf::from("/shared/libraries/a/a.jar")
+        );
+        assert_eq!(
+            SharedLibraries.client_jar(base_path, "1.20.1"),
+            VanillaPaths.client_jar(base_path, "1.20.1")
+        );
+        assert_eq!(
+            VanillaPaths.asset_object(base_path, "bdf48ef6"),
+            base_path.join("assets/objects/bd/bdf48ef6")
+        );
+    }
+
+    #[test]
+    fn library_paths_use_the_platform_separator() {
+        let path = VanillaPaths.library(Path::new("base"), "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1.jar");
+
+        assert_eq!(
+            path,
+            [
+                "base",
+                "libraries",
+                "org",
+                "lwjgl",
+                "lwjgl",
+                "3.3.1",
+                "lwjgl-3.3.1.jar"
+            ]
+            .iter()
+            .collect::<PathBuf>()
+        );
+        let foreign = if cfg!(windows) { '/' } else { '\\' };
+        assert!(!path.to_string_lossy().contains(foreign));
+        assert_eq!(
+            join_maven_path(Path::new("base"), "a//b/"),
+            Path::new("base").join("a").join("b")
+        );
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use zip::ZipArchive;
+
+use crate::error::ClientDownloaderError;
+use crate::launch::CLASSPATH_SEPARATOR;
+use crate::manifest::{maven_to_path, ForgeInstallProfile, ForgeProcessor};
+
+use super::client_downloader::parent_dir;
+use super::paths::join_maven_path;
+use super::{verify_file, VerifyStatus};
+
+/// The path of a `group:artifact:version[:classifier][@extension]` library.
+fn library_path(libraries_path: &Path, coordinate: &str) -> Result<PathBuf, ClientDownloaderError> {
+    let (coordinate, extension) = coordinate.split_once('@').unwrap_or((coordinate, "jar"));
+    let path = maven_to_path(coordinate)?;
+    let path = format!("{}.{extension}", path.trim_end_matches(".jar"));
+
+    Ok(join_maven_path(libraries_path, &path))
+}
+
+/// Resolves a processor argument or a `data` value: `[coordinate]` is the
+/// path of a library, `{KEY}` the value of `data`, `'literal'` is taken as
+/// is.
+fn resolve(
+    value: &str,
+    libraries_path: &Path,
+    data: &HashMap<String, String>,
+) -> Result<String, ClientDownloaderError> {
+    if let Some(coordinate) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        return Ok(library_path(libraries_path, coordinate)?
+            .to_string_lossy()
+            .to_string());
+    }
+    if let Some(key) = value.strip_prefix('{').and_then(|v| v.strip_suffix('}')) {
+        return data
+            .get(key)
+            .cloned()
+            .ok_or_else(|| ClientDownloaderError::Processor(format!("missing data {key}")));
+    }
+    if let Some(literal) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        return Ok(literal.to_string());
+    }
+
+    Ok(value.to_string())
+}
+
+/// The client `data` of the profile, with the files it names extracted
+/// from the installer into `data_path`.
+fn client_data(
+    profile: &ForgeInstallProfile,
+    base_path: &Path,
+    libraries_path: &Path,
+    installer: &Path,
+    minecraft_jar: &Path,
+    data_path: &Path,
+) -> Result<HashMap<String, String>, ClientDownloaderError> {
+    let path = |path: &Path| path.to_string_lossy().to_string();
+    let mut data = HashMap::from([
+        ("SIDE".to_string(), "client".to_string()),
+        ("MINECRAFT_JAR".to_string(), path(minecraft_jar)),
+        ("MINECRAFT_VERSION".to_string(), profile.minecraft.clone()),
+        ("ROOT".to_string(), path(base_path)),
+        ("INSTALLER".to_string(), path(installer)),
+        ("LIBRARY_DIR".to_string(), path(libraries_path)),
+    ]);
+
+    let mut archive = ZipArchive::new(File::open(installer)?)?;
+    for (key, value) in &profile.data {
+        let value = match value.client.strip_prefix('/') {
+            Some(entry) => {
+                let output_path = data_path.join(entry);
+                std::fs::create_dir_all(parent_dir(&output_path)?)?;
+                std::io::copy(
+                    &mut archive.by_name(entry)?,
+                    &mut File::create(&output_path)?,
+                )?;
+                path(&output_path)
+            }
+            None => resolve(&value.client, libraries_path, &data)?,
+        };
+        data.insert(key.clone(), value);
+    }
+
+    Ok(data)
+}
+
+/// The `Main-Class` of the manifest of `jar`.
+fn main_class(jar: &Path) -> Result<String, ClientDownloaderError> {
+    let mut archive = ZipArchive::new(File::open(jar)?)?;
+    let mut manifest = String::new();
+    archive
+        .by_name("META-INF/MANIFEST.MF")?
+        .read_to_string(&mut manifest)?;
+
+    manifest
+        .lines()
+        .find_map(|line| line.strip_prefix("Main-Class:"))
+        .map(|class| class.trim().to_string())
+        .ok_or_else(|| {
+            ClientDownloaderError::Processor(format!("{} has no main class", jar.display()))
+        })
+}
+
+/// Whether every output of the processor is already there with the right
+/// hash, processors without outputs always run.
+fn outputs_valid(
+    processor: &ForgeProcessor,
+    libraries_path: &Path,
+    data: &HashMap<String, String>,
+) -> Result<bool, ClientDownloaderError> {
+    if processor.outputs.is_empty() {
+        return Ok(false);
+    }
+
+    for (file, sha1) in &processor.outputs {
+        let file = resolve(file, libraries_path, data)?;
+        let sha1 = resolve(sha1, libraries_path, data)?;
+        if verify_file(&sha1, PathBuf::from(file)) != VerifyStatus::Ok {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+fn run_processor(
+    processor: &ForgeProcessor,
+    libraries_path: &Path,
+    data: &HashMap<String, String>,
+    java: &Path,
+) -> Result<(), ClientDownloaderError> {
+    let jar = library_path(libraries_path, &processor.jar)?;
+    let main_class = main_class(&jar)?;
+
+    let mut classpath = vec![jar.to_string_lossy().to_string()];
+    for library in &processor.classpath {
+        classpath.push(
+            library_path(libraries_path, library)?
+                .to_string_lossy()
+                .to_string(),
+        );
+    }
+    let args = processor
+        .args
+        .iter()
+        .map(|arg| resolve(arg, libraries_path, data))
+        .collect::<Result<Vec<String>, _>>()?;
+
+    let status = Command::new(java)
+        .arg("-cp")
+        .arg(classpath.join(CLASSPATH_SEPARATOR))
+        .arg(&main_class)
+        .args(args)
+        .status()?;
+    if !status.success() {
+        return Err(ClientDownloaderError::Processor(format!(
+            "{} exited with {status}",
+            processor.jar
+        )));
+    }
+
+    if !processor.outputs.is_empty() && !outputs_valid(processor, libraries_path, data)? {
+        return Err(ClientDownloaderError::Processor(format!(
+            "{} produced invalid outputs",
+            processor.jar
+        )));
+    }
+
+    Ok(())
+}
+
+/// Runs the client processors of a Forge or NeoForge install profile with
+/// `java`, e.g. to patch the vanilla jar into the Forge client.
+///
+/// The libraries of the profile must already be in `libraries_path`,
+/// `installer` is the installer jar the profile comes from, the `path` of
+/// the `ForgeInstaller` of `download_version`, and
+/// `minecraft_jar` the vanilla client jar. Processors whose outputs are
+/// already valid are skipped.
+pub fn run_processors(
+    profile: &ForgeInstallProfile,
+    base_path: &PathBuf,
+    libraries_path: &Path,
+    installer: &Path,
+    minecraft_jar: &Path,
+    java: &Path,
+) -> Result<(), ClientDownloaderError> {
+    let data_path = base_path.join("forge_installer_data");
+    let data = client_data(
+        profile,
+        base_path,
+        libraries_path,
+        installer,
+        minecraft_jar,
+        &data_path,
+    )?;
+
+    let result = profile
+        .processors
+        .iter()
+        .filter(|processor| {
+            processor
+                .sides
+                .as_ref()
+                .is_none_or(|sides| sides.iter().any(|side| side == "client"))
+        })
+        .try_for_each(|processor| {
+            if outputs_valid(processor, libraries_path, &data)? {
+                return Ok(());
+            }
+            run_processor(processor, libraries_path, &data, java)
+        });
+
+    if data_path.exists() {
+        std::fs::remove_dir_all(&data_path)?;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Write;
+    use std::path::Path;
+
+    use serde_json::json;
+
+    use super::{client_data, resolve};
+    use crate::client::{ClientDownloader, DownloadVersion, MirrorConfig};
+    use crate::manifest::Manifest;
+
+    /// An installer jar with the layout of the Forge ones.
+    fn forge_installer() -> Vec<u8> {
+        let library = |name: &str, path: &str| {
+            json!({
+                "name": name,
+                "downloads": {"artifact": {
+                    "path": path,
+                    "sha1": "",
+                    "size": 1,
+                    "url": format!("https://maven.minecraftforge.net/{path}")
+                }}
+            })
+        };
+        let version = json!({
+            "id": "1.20.1-forge-47.1.0",
+            "inheritsFrom": "1.20.1",
+            "libraries": [library(
+                "net.minecraftforge:fmlloader:1.20.1-47.1.0",
+                "net/minecraftforge/fmlloader/1.20.1-47.1.0/fmlloader-1.20.1-47.1.0.jar"
+            )],
+            "mainClass": "cpw.mods.bootstraplauncher.BootstrapLauncher",
+            "releaseTime": "2023-06-12T13:25:51+00:00",
+            "time": "2023-06-12T13:25:51+00:00",
+            "type": "release"
+        });
+        let profile = json!({
+            "minecraft": "1.20.1",
+            "libraries": [library(
+                "net.minecraftforge:installertools:1.3.0",
+                "net/minecraftforge/installertools/1.3.0/installertools-1.3.0.jar"
+            )],
+            "data": {
+                "BINPATCH": {"client": "/data/client.lzma", "server": "/data/server.lzma"},
+                "MAPPINGS": {
+                    "client": "[de.oceanlabs.mcp:mcp_config:1.20.1:mappings@txt]",
+                    "server": "[de.oceanlabs.mcp:mcp_config:1.20.1:mappings@txt]"
+                }
+            },
+            "processors": []
+        });
+
+        let mut jar = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, content) in [
+            ("version.json", version.to_string()),
+            ("install_profile.json", profile.to_string()),
+            ("data/client.lzma", "patches".to_string()),
+        ] {
+            jar.start_file(name, Default::default()).unwrap();
+            jar.write_all(content.as_bytes()).unwrap();
+        }
+        jar.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn forge_setup_leads_to_the_processor_data() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock(
+                "GET",
+                "/net/minecraftforge/forge/1.20.1-47.1.0/forge-1.20.1-47.1.0-installer.jar",
+            )
+            .with_body(forge_installer())
+            .create();
+
+        let mut downloader = ClientDownloader::from_manifest(
+            serde_json::from_value(
+                json!({"latest": {"release": "", "snapshot": ""}, "versions": []}),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        downloader.with_mirror(MirrorConfig {
+            forge_maven: Some(server.url()),
+            ..Default::default()
+        });
+        let file = json!({"sha1": "", "size": 1, "url": ""});
+        let mut vanilla: Manifest = serde_json::from_value(json!({
+            "assetIndex": {"id": "5", "sha1": "", "size": 1, "totalSize": 1, "url": ""},
+            "assets": "5",
+            "downloads": {"client": file, "server": file},
+            "id": "1.20.1",
+            "libraries": [],
+            "mainClass": "net.minecraft.client.main.Main",
+            "releaseTime": "2023-06-12T13:25:51+00:00",
+            "time": "2023-06-12T13:25:51+00:00",
+            "type": "release"
+        }))
+        .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().join(".minecraft");
+        let (manifest, installer) = downloader
+            .setup_forge("1.20.1", "47.1.0", &mut vanilla, &base_path)
+            .unwrap();
+        mock.assert();
+
+        let libraries_path = base_path.join("libraries");
+        assert_eq!(
+            installer.path,
+            libraries_path
+                .join("net/minecraftforge/forge/1.20.1-47.1.0/forge-1.20.1-47.1.0-installer.jar")
+        );
+        assert!(installer.path.is_file());
+        assert_eq!(
+            manifest.main_class,
+            "cpw.mods.bootstraplauncher.BootstrapLauncher"
+        );
+        let names = |libraries: &[crate::manifest::ManifestLibrary]| {
+            libraries.iter().map(|l| l.name.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&installer.libraries),
+            ["net.minecraftforge:installertools:1.3.0"]
+        );
+
+        let minecraft_jar = base_path.join("versions/1.20.1/1.20.1.jar");
+        let data_path = base_path.join("forge_installer_data");
+        let data = client_data(
+            &installer.profile,
+            &base_path,
+            &libraries_path,
+            &installer.path,
+            &minecraft_jar,
+            &data_path,
+        )
+        .unwrap();
+
+        let binpatch = data_path.join("data/client.lzma");
+        assert_eq!(data["BINPATCH"], binpatch.to_string_lossy());
+        assert_eq!(std::fs::read_to_string(binpatch).unwrap(), "patches");
+        assert_eq!(data["SIDE"], "client");
+        assert_eq!(data["MINECRAFT_JAR"], minecraft_jar.to_string_lossy());
+        assert!(data["MAPPINGS"].ends_with("mcp_config-1.20.1-mappings.txt"));
+    }
+
+    #[test]
+    fn processor_arguments_are_resolved() {
+        let libraries_path = Path::new("/tmp/.minecraft/libraries");
+        let data = HashMap::from([("SIDE".to_string(), "client".to_string())]);
+        let resolve = |value| resolve(value, libraries_path, &data).unwrap();
+
+        assert_eq!(resolve("{SIDE}"), "client");
+        assert_eq!(resolve("'abc'"), "abc");
+        assert_eq!(resolve("--task"), "--task");
+        assert_eq!(
+            resolve("[net.minecraftforge:installertools:1.3.0]"),
+            "/tmp/.minecraft/libraries/net/minecraftforge/installertools/1.3.0/installertools-1.3.0.jar"
+        );
+        assert_eq!(
+            resolve("[de.oceanlabs.mcp:mcp_config:1.20.1:mappings@txt]"),
+            "/tmp/.minecraft/libraries/de/oceanlabs/mcp/mcp_config/1.20.1/mcp_config-1.20.1-mappings.txt"
+        );
+    }
+}
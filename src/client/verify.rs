@@ -1,6 +1,8 @@
-use chksum::sha1;
+use chksum::{sha1, sha2_256};
 use std::path::PathBuf;
 
+use crate::error::DownloadError;
+
 #[derive(Clone, Default, Debug, Eq, PartialEq)]
 pub enum VerifyStatus {
     /// The file has not been verified
@@ -12,6 +14,13 @@ pub enum VerifyStatus {
     Ok,
 }
 
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+}
+
 impl std::fmt::Display for VerifyStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -26,17 +35,161 @@ impl std::fmt::Display for VerifyStatus {
     }
 }
 
+fn compare_digest(digest: Option<String>, expected_hash: &str) -> VerifyStatus {
+    match digest {
+        Some(digest) if digest == expected_hash.trim().to_lowercase() => VerifyStatus::Ok,
+        _ => VerifyStatus::Failed,
+    }
+}
+
+/// Compares an already computed `digest` to the expected hash.
+pub(super) fn verify_digest(expected_hash: &str, digest: String) -> VerifyStatus {
+    if expected_hash.trim().is_empty() {
+        return VerifyStatus::NotVerified;
+    }
+
+    compare_digest(Some(digest), expected_hash)
+}
+
+/// The SHA-1 of the file, as lowercase hex.
+pub fn compute_sha1(path: &PathBuf) -> Result<String, DownloadError> {
+    sha1::chksum(path)
+        .map(|digest| digest.to_hex_lowercase())
+        .map_err(|e| DownloadError::Checksum(path.clone(), e.to_string()))
+}
+
+/// The SHA-256 of the file, as lowercase hex.
+pub fn compute_sha256(path: &PathBuf) -> Result<String, DownloadError> {
+    sha2_256::chksum(path)
+        .map(|digest| digest.to_hex_lowercase())
+        .map_err(|e| DownloadError::Checksum(path.clone(), e.to_string()))
+}
+
 pub fn verify_file(expected_hash: &str, path: PathBuf) -> VerifyStatus {
-    // Try to compute the SHA-1 hash of the file
-    match sha1::chksum(&path) {
-        Ok(digest) => {
-            // Compare with the expected hash
-            if digest.to_hex_lowercase() == expected_hash.to_lowercase() {
-                VerifyStatus::Ok
-            } else {
-                VerifyStatus::Failed
-            }
-        }
-        Err(_) => VerifyStatus::Failed,
+    // Nothing to compare against, hashing the file would be pointless
+    if expected_hash.trim().is_empty() {
+        return VerifyStatus::NotVerified;
+    }
+
+    // Try to compute the SHA-1 hash of the file and compare it
+    compare_digest(compute_sha1(&path).ok(), expected_hash)
+}
+
+/// Verifies in-memory data against its SHA-1 hash.
+pub fn verify_bytes(expected_hash: &str, bytes: &[u8]) -> VerifyStatus {
+    if expected_hash.trim().is_empty() {
+        return VerifyStatus::NotVerified;
+    }
+
+    let digest = sha1::chksum(bytes).ok().map(|d| d.to_hex_lowercase());
+    compare_digest(digest, expected_hash)
+}
+
+/// Verifies the file against a hash of the given algorithm.
+///
+/// An empty `expected_hash` can't be verified and gives `NotVerified`.
+pub fn verify_file_with(
+    algorithm: HashAlgorithm,
+    expected_hash: &str,
+    path: PathBuf,
+) -> VerifyStatus {
+    if expected_hash.trim().is_empty() {
+        return VerifyStatus::NotVerified;
+    }
+
+    match algorithm {
+        HashAlgorithm::Sha1 => verify_file(expected_hash, path),
+        HashAlgorithm::Sha256 => compare_digest(compute_sha256(&path).ok(), expected_hash),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{
+        compute_sha1, compute_sha256, verify_bytes, verify_file, verify_file_with, HashAlgorithm,
+        VerifyStatus,
+    };
+    use crate::error::DownloadError;
+
+    const HELLO_SHA1: &str = "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d";
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    fn hello_file() -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"hello").unwrap();
+        file
+    }
+
+    #[test]
+    fn verify_empty_hash() {
+        let file = hello_file();
+        let path = file.path().to_path_buf();
+
+        assert_eq!(verify_file("", path.clone()), VerifyStatus::NotVerified);
+        assert_eq!(verify_file("  ", path), VerifyStatus::NotVerified);
+    }
+
+    #[test]
+    fn verify_sha1() {
+        let file = hello_file();
+        let path = file.path().to_path_buf();
+
+        assert_eq!(
+            verify_file_with(HashAlgorithm::Sha1, HELLO_SHA1, path.clone()),
+            VerifyStatus::Ok
+        );
+        assert_eq!(
+            verify_file_with(HashAlgorithm::Sha1, HELLO_SHA256, path),
+            VerifyStatus::Failed
+        );
+    }
+
+    #[test]
+    fn verify_sha256() {
+        let file = hello_file();
+        let path = file.path().to_path_buf();
+
+        assert_eq!(
+            verify_file_with(
+                HashAlgorithm::Sha256,
+                &HELLO_SHA256.to_uppercase(),
+                path.clone()
+            ),
+            VerifyStatus::Ok
+        );
+        assert_eq!(
+            verify_file_with(HashAlgorithm::Sha256, HELLO_SHA1, path.clone()),
+            VerifyStatus::Failed
+        );
+        assert_eq!(
+            verify_file_with(HashAlgorithm::Sha256, "", path),
+            VerifyStatus::NotVerified
+        );
+    }
+
+    #[test]
+    fn verify_in_memory_bytes() {
+        assert_eq!(verify_bytes(HELLO_SHA1, b"hello"), VerifyStatus::Ok);
+        assert_eq!(verify_bytes(HELLO_SHA1, b"hello!"), VerifyStatus::Failed);
+        assert_eq!(verify_bytes("", b"hello"), VerifyStatus::NotVerified);
+        assert_eq!(
+            verify_bytes(&format!(" {HELLO_SHA1}\n"), b"hello"),
+            VerifyStatus::Ok
+        );
+    }
+
+    #[test]
+    fn computed_digests() {
+        let file = hello_file();
+        let path = file.path().to_path_buf();
+
+        assert_eq!(compute_sha1(&path).unwrap(), HELLO_SHA1);
+        assert_eq!(compute_sha256(&path).unwrap(), HELLO_SHA256);
+        assert!(matches!(
+            compute_sha1(&path.join("missing")),
+            Err(DownloadError::Checksum(..))
+        ));
     }
 }
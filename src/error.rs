@@ -1,6 +1,8 @@
+use std::path::PathBuf;
+
 use thiserror::Error;
 
-use crate::client::DownloadOutput;
+use crate::client::{DownloadOutput, Launcher};
 
 #[derive(Error, Debug)]
 pub enum ClientDownloaderError {
@@ -13,8 +15,44 @@ pub enum ClientDownloaderError {
     #[error("No such directory")]
     NoSuchDirectory,
 
+    #[error("The {0:?} launcher needs a loader version.")]
+    MissingLauncherId(Launcher),
+
+    #[error("Invalid path: {0}")]
+    InvalidPath(PathBuf),
+
+    #[error("The manifest of version {0} doesn't match its checksum.")]
+    ManifestChecksumMismatch(String),
+
+    #[error("The Java archive {0} doesn't match its checksum.")]
+    JavaChecksumMismatch(PathBuf),
+
+    #[error("The Java archive {0} is invalid.")]
+    InvalidJavaArchive(PathBuf),
+
+    #[error("The cached version manifest {0} doesn't exist.")]
+    NoSuchCache(PathBuf),
+
+    #[error("The cached version manifest {0} is invalid: {1}")]
+    InvalidCache(PathBuf, serde_json::Error),
+
+    #[error("All the {} downloads failed.", .0.len())]
+    AllDownloadsFailed(Vec<DownloadError>),
+
+    #[error("Forge processor failed: {0}")]
+    Processor(String),
+
+    #[error("The request timed out: {0}")]
+    Timeout(reqwest::Error),
+
+    #[error("{url} answered with {status}")]
+    HttpStatus {
+        url: String,
+        status: reqwest::StatusCode,
+    },
+
     #[error("{0}")]
-    Request(#[from] reqwest::Error),
+    Request(reqwest::Error),
 
     #[error("{0}")]
     IO(#[from] std::io::Error),
@@ -24,6 +62,25 @@ pub enum ClientDownloaderError {
 
     #[error("{0}")]
     Download(#[from] DownloadError),
+
+    #[error("{0}")]
+    Manifest(#[from] ManifestError),
+
+    #[error("{0}")]
+    Zip(#[from] zip::result::ZipError),
+}
+
+impl From<reqwest::Error> for ClientDownloaderError {
+    fn from(error: reqwest::Error) -> Self {
+        match (error.status(), error.url()) {
+            _ if error.is_timeout() => Self::Timeout(error),
+            (Some(status), Some(url)) => Self::HttpStatus {
+                url: url.to_string(),
+                status,
+            },
+            _ => Self::Request(error),
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -37,6 +94,15 @@ pub enum ManifestError {
     #[error("An unexpected error has ocurred.")]
     UnknownError,
 
+    #[error("Invalid maven coordinate: {0}")]
+    InvalidCoordinate(String),
+
+    #[error("Unknown version type: {0}")]
+    UnknownVersionType(String),
+
+    #[error("Version {0} inherits from itself.")]
+    InheritanceCycle(String),
+
     #[error("{0}")]
     IO(#[from] std::io::Error),
 
@@ -61,4 +127,30 @@ pub enum DownloadError {
     /// Download file verification failed.
     #[error("Verification failed for {0}")]
     Verification(DownloadOutput),
+    /// The download was cancelled before the file was complete.
+    #[error("Download cancelled for {0}")]
+    Cancelled(DownloadOutput),
+    /// The hash of a file couldn't be computed.
+    #[error("Checksum of {0} failed: {1}")]
+    Checksum(PathBuf, String),
+    /// The runtime the downloads run on couldn't be started.
+    #[error("Download runtime failed to start: {0}")]
+    Runtime(std::io::Error),
+    /// The task running the downloads panicked.
+    #[error("{0}")]
+    Join(#[from] tokio::task::JoinError),
+}
+
+impl DownloadError {
+    /// The outcome of the file the error is about, `None` for the errors
+    /// that aren't about a single file.
+    pub fn output(&self) -> Option<&DownloadOutput> {
+        match self {
+            Self::File(output)
+            | Self::Download(output)
+            | Self::Verification(output)
+            | Self::Cancelled(output) => Some(output),
+            _ => None,
+        }
+    }
 }
@@ -1,8 +1,13 @@
 use std::collections::BTreeMap;
+use std::path::Path;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Serialize)]
+use crate::error::ClientDownloaderError;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 #[allow(non_snake_case)]
 pub struct ProfileJson {
     pub profiles: BTreeMap<String, Profiles>,
@@ -15,9 +20,13 @@ pub struct ProfileJson {
     pub analyticsFailcount: Option<i32>,
     pub selectedUser: Option<SelectedUser>,
     pub version: Option<i32>,
+    /// The fields of the file this crate doesn't know, kept as they are.
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>,
 }
 
-#[derive(Serialize)]
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
 #[allow(non_snake_case)]
 pub struct Profiles {
     pub name: String,
@@ -32,15 +41,18 @@ pub struct Profiles {
     pub logConfig: Option<String>,
     pub logConfigIsXML: Option<bool>,
     pub resolution: Option<Resolution>,
+    /// The fields of the profile this crate doesn't know, kept as they are.
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Resolution {
     pub height: i32,
     pub width: i32,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_snake_case)]
 pub struct AuthenticationDatabase {
     pub accessToken: String,
@@ -49,7 +61,7 @@ pub struct AuthenticationDatabase {
     pub profiles: BTreeMap<String, String>,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_snake_case)]
 pub struct LauncherVersion {
     pub name: String,
@@ -57,7 +69,8 @@ pub struct LauncherVersion {
     pub profilesFormat: i32,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 #[allow(non_snake_case)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct Settings {
@@ -74,7 +87,7 @@ pub struct Settings {
     pub soundOn: Option<bool>,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SelectedUser {
     pub account: String,
     pub profile: String,
@@ -87,23 +100,135 @@ impl Default for ProfileJson {
             clientToken: None,
             authenticationDatabase: None,
             launcherVersion: None,
-            settings: Settings {
-                enableSnapshots: true,
-                enableAdvanced: true,
-                keepLauncherOpen: true,
-                showGameLog: true,
-                locale: None,
-                showMenu: true,
-                enableHistorical: true,
-                profileSorting: "ByLastPlayed".to_owned(),
-                crashAssistance: false,
-                enableAnalytics: false,
-                soundOn: Some(false),
-            },
+            settings: Settings::default(),
             analyticsToken: None,
             analyticsFailcount: None,
             selectedUser: None,
             version: None,
+            other: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            enableSnapshots: true,
+            enableAdvanced: true,
+            keepLauncherOpen: true,
+            showGameLog: true,
+            locale: None,
+            showMenu: true,
+            enableHistorical: true,
+            profileSorting: "ByLastPlayed".to_owned(),
+            crashAssistance: false,
+            enableAnalytics: false,
+            soundOn: Some(false),
+        }
+    }
+}
+
+impl ProfileJson {
+    /// Reads a `launcher_profiles.json`, the default when there is no such
+    /// file.
+    pub fn load(path: &Path) -> Result<Self, ClientDownloaderError> {
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ClientDownloaderError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Adds the profile `id`, replacing the one with the same id.
+    pub fn add_profile(&mut self, id: &str, profile: Profiles) -> Option<Profiles> {
+        self.profiles.insert(id.to_string(), profile)
+    }
+
+    pub fn remove_profile(&mut self, id: &str) -> Option<Profiles> {
+        self.profiles.remove(id)
+    }
+
+    pub fn profile_mut(&mut self, id: &str) -> Option<&mut Profiles> {
+        self.profiles.get_mut(id)
+    }
+
+    /// Sets when the profile `id` was last played, e.g.
+    /// `2023-06-12T13:25:51.000Z`. `false` if there is no such profile.
+    pub fn set_last_used(&mut self, id: &str, last_used: &str) -> bool {
+        let Some(profile) = self.profiles.get_mut(id) else {
+            return false;
+        };
+        profile.lastUsed = Some(last_used.to_string());
+        true
+    }
+
+    /// Adds a profile for the version `version_id`, or points the existing
+    /// one at it again.
+    pub fn add_version(&mut self, version_id: &str) {
+        let profile = self
+            .profiles
+            .entry(version_id.to_string())
+            .or_insert_with(|| Profiles::new(version_id, version_id));
+        profile.lastVersionId = version_id.to_string();
+    }
+}
+
+impl Profiles {
+    /// A custom profile named `name` that starts `version_id`.
+    pub fn new(name: &str, version_id: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            r#type: Some("custom".to_string()),
+            lastVersionId: version_id.to_string(),
+            ..Default::default()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ProfileJson, Profiles};
+
+    #[test]
+    fn existing_profiles_are_kept() {
+        let mut profiles: ProfileJson = serde_json::from_str(
+            r#"{
+                "profiles": {"mine": {"name": "Mine", "lastVersionId": "1.8.9", "skin": "steve"}},
+                "clientToken": "token",
+                "settings": {"locale": "fi-fi"}
+            }"#,
+        )
+        .unwrap();
+        profiles.add_version("1.20.1");
+
+        let json = serde_json::to_value(&profiles).unwrap();
+        assert_eq!(json["profiles"]["mine"]["lastVersionId"], "1.8.9");
+        assert_eq!(json["profiles"]["mine"]["skin"], "steve");
+        assert_eq!(json["profiles"]["1.20.1"]["lastVersionId"], "1.20.1");
+        assert_eq!(json["clientToken"], "token");
+        assert_eq!(json["settings"]["locale"], "fi-fi");
+    }
+
+    #[test]
+    fn profiles_are_added_and_removed() {
+        let mut profiles = ProfileJson::default();
+        profiles.add_profile(
+            "modded",
+            Profiles::new("Modded", "fabric-loader-0.15.0-1.20.1"),
+        );
+
+        assert!(profiles.set_last_used("modded", "2023-06-12T13:25:51.000Z"));
+        assert!(!profiles.set_last_used("vanilla", "2023-06-12T13:25:51.000Z"));
+        assert_eq!(
+            profiles.profiles["modded"].lastUsed.as_deref(),
+            Some("2023-06-12T13:25:51.000Z")
+        );
+        assert!(profiles.remove_profile("modded").is_some());
+        assert!(profiles.profiles.is_empty());
+    }
+}
@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::manifest::{Arguments, Features, JvmArgument, Manifest, Os, Rules};
+
+/// Where the game goes straight to once started.
+#[derive(Clone, Debug)]
+pub enum QuickPlay {
+    /// The name of the world directory.
+    Singleplayer(String),
+    /// The `host:port` of the server.
+    Multiplayer(String),
+    /// The id of the realm.
+    Realms(String),
+}
+
+/// The size of the game window.
+#[derive(Clone, Copy, Debug)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The values the launch arguments of a `Manifest` are filled with.
+#[derive(Clone, Debug, Default)]
+pub struct LaunchContext {
+    pub auth_player_name: String,
+    pub auth_uuid: String,
+    pub auth_access_token: String,
+    pub user_type: String,
+    pub game_directory: PathBuf,
+    pub assets_root: PathBuf,
+    pub natives_directory: PathBuf,
+    pub library_directory: PathBuf,
+    /// The libraries and the client jar, in order.
+    pub classpath: Vec<PathBuf>,
+    pub launcher_name: String,
+    pub launcher_version: String,
+    /// The features enabled for the feature gated arguments.
+    pub features: Features,
+    /// Enables the quick play feature matching it.
+    pub quick_play: Option<QuickPlay>,
+    /// The file the game logs the quick play sessions to.
+    pub quick_play_path: Option<PathBuf>,
+    /// JVM arguments put before the ones of the manifest, e.g. the
+    /// `authlib_injector_arg` of a third party auth server.
+    pub extra_jvm_args: Vec<String>,
+    /// Starts the game in demo mode.
+    pub demo: bool,
+    /// Enables the custom resolution arguments.
+    pub resolution: Option<Resolution>,
+}
+
+pub(crate) const CLASSPATH_SEPARATOR: &str = if cfg!(windows) { ";" } else { ":" };
+
+fn rule_applies(rule: &Rules, os: &Os, features: &Features) -> bool {
+    // The `version` of the rule is a regex of the os version, it isn't
+    // checked so those rules match any version.
+    if let Some(rule_os) = &rule.os {
+        let matches = |required: &Option<String>, actual: &Option<String>| {
+            required.is_none() || required == actual
+        };
+        if !matches(&rule_os.name, &os.name) || !matches(&rule_os.arch, &os.arch) {
+            return false;
+        }
+    }
+
+    let Some(required) = &rule.features else {
+        return true;
+    };
+    let enabled = |required: Option<bool>, enabled: Option<bool>| {
+        required.is_none_or(|required| required == enabled.unwrap_or(false))
+    };
+    enabled(required.is_demo_user, features.is_demo_user)
+        && enabled(
+            required.has_custom_resolution,
+            features.has_custom_resolution,
+        )
+        && enabled(
+            required.has_quick_plays_support,
+            features.has_quick_plays_support,
+        )
+        && enabled(
+            required.is_quick_play_singleplayer,
+            features.is_quick_play_singleplayer,
+        )
+        && enabled(
+            required.is_quick_play_multiplayer,
+            features.is_quick_play_multiplayer,
+        )
+        && enabled(required.is_quick_play_realms, features.is_quick_play_realms)
+}
+
+/// The features of `ctx`, with the ones its demo mode, resolution and quick
+/// play need enabled.
+fn launch_features(ctx: &LaunchContext) -> Features {
+    let mut features = ctx.features.clone();
+    if ctx.demo {
+        features.is_demo_user = Some(true);
+    }
+    if ctx.resolution.is_some() {
+        features.has_custom_resolution = Some(true);
+    }
+    if ctx.quick_play_path.is_some() {
+        features.has_quick_plays_support = Some(true);
+    }
+    match ctx.quick_play {
+        Some(QuickPlay::Singleplayer(_)) => features.is_quick_play_singleplayer = Some(true),
+        Some(QuickPlay::Multiplayer(_)) => features.is_quick_play_multiplayer = Some(true),
+        Some(QuickPlay::Realms(_)) => features.is_quick_play_realms = Some(true),
+        None => {}
+    }
+    features
+}
+
+fn argument_allowed(rules: &[Rules], os: &Os, features: &Features) -> bool {
+    // Same as for the libraries, the last matching rule decides
+    rules
+        .iter()
+        .rfind(|rule| rule_applies(rule, os, features))
+        .is_some_and(|rule| rule.action == "allow")
+}
+
+/// Flattens the arguments, keeping the rule gated ones only when their rules
+/// allow them on `os` with the enabled `features`.
+pub fn arguments_for(args: &[JvmArgument], os: &Os, features: &Features) -> Vec<String> {
+    let mut flattened = Vec::new();
+
+    for arg in args {
+        match arg {
+            JvmArgument::String(arg) => flattened.push(arg.clone()),
+            JvmArgument::Struct { rules, value } if argument_allowed(rules, os, features) => {
+                match value {
+                    Value::String(value) => flattened.push(value.clone()),
+                    Value::Array(values) => flattened
+                        .extend(values.iter().filter_map(|v| v.as_str().map(str::to_string))),
+                    _ => {}
+                }
+            }
+            JvmArgument::Struct { .. } => {}
+        }
+    }
+
+    flattened
+}
+
+/// Replaces every known `${name}` placeholder of `arg` in a single pass,
+/// the substituted values aren't expanded again. Unknown ones are kept.
+fn substitute(arg: &str, values: &HashMap<&str, String>) -> String {
+    let mut substituted = String::with_capacity(arg.len());
+    let mut rest = arg;
+
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        substituted.push_str(&rest[..start]);
+        match values.get(&rest[start + 2..end]) {
+            Some(value) => substituted.push_str(value),
+            None => substituted.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    substituted.push_str(rest);
+    substituted
+}
+
+/// The JVM argument loading the authlib-injector agent `jar`, authenticating
+/// against the auth server at `server_url` instead of Mojang's.
+pub fn authlib_injector_arg(jar: &Path, server_url: &str) -> String {
+    format!("-javaagent:{}={server_url}", jar.to_string_lossy())
+}
+
+/// The arguments of a version before 1.13: its `minecraftArguments` and the
+/// JVM arguments the launcher used to add itself.
+fn legacy_arguments(minecraft_arguments: &str) -> Arguments {
+    let string = |arg: &str| JvmArgument::String(arg.to_string());
+
+    Arguments {
+        game: minecraft_arguments.split_whitespace().map(string).collect(),
+        jvm: [
+            "-Djava.library.path=${natives_directory}",
+            "-cp",
+            "${classpath}",
+        ]
+        .into_iter()
+        .map(string)
+        .collect(),
+    }
+}
+
+/// Builds the arguments to start the game with, without the java binary:
+/// the extra and the manifest JVM arguments, the logging configuration, the
+/// main class and then the game arguments.
+pub fn build_launch_args(manifest: &Manifest, ctx: &LaunchContext) -> Vec<String> {
+    let path = |path: &PathBuf| path.to_string_lossy().to_string();
+    let classpath = ctx
+        .classpath
+        .iter()
+        .map(path)
+        .collect::<Vec<String>>()
+        .join(CLASSPATH_SEPARATOR);
+
+    // The serialized name, e.g. `old_alpha`, not the display one
+    let version_type = serde_json::to_value(&manifest.type_)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default();
+
+    let mut quick_play = [String::new(), String::new(), String::new()];
+    match &ctx.quick_play {
+        Some(QuickPlay::Singleplayer(world)) => quick_play[0] = world.clone(),
+        Some(QuickPlay::Multiplayer(server)) => quick_play[1] = server.clone(),
+        Some(QuickPlay::Realms(realm)) => quick_play[2] = realm.clone(),
+        None => {}
+    }
+    let [singleplayer, multiplayer, realms] = quick_play;
+    let (width, height) = ctx
+        .resolution
+        .map(|r| (r.width.to_string(), r.height.to_string()))
+        .unwrap_or_default();
+
+    // The assets the versions before 1.7 read directly, see
+    // `copy_virtual_assets`
+    let game_assets = match manifest.assets.as_str() {
+        "pre-1.6" => ctx.game_directory.join("resources"),
+        "legacy" => ctx.assets_root.join("virtual").join("legacy"),
+        _ => ctx.assets_root.clone(),
+    };
+
+    let values = HashMap::from([
+        ("auth_player_name", ctx.auth_player_name.clone()),
+        ("auth_uuid", ctx.auth_uuid.clone()),
+        ("auth_access_token", ctx.auth_access_token.clone()),
+        (
+            "auth_session",
+            format!("token:{}:{}", ctx.auth_access_token, ctx.auth_uuid),
+        ),
+        ("user_properties", "{}".to_string()),
+        ("game_assets", path(&game_assets)),
+        ("user_type", ctx.user_type.clone()),
+        ("version_name", manifest.id.clone()),
+        ("version_type", version_type),
+        ("game_directory", path(&ctx.game_directory)),
+        ("assets_root", path(&ctx.assets_root)),
+        ("assets_index_name", manifest.asset_index.id.clone()),
+        ("natives_directory", path(&ctx.natives_directory)),
+        ("library_directory", path(&ctx.library_directory)),
+        ("launcher_name", ctx.launcher_name.clone()),
+        ("launcher_version", ctx.launcher_version.clone()),
+        ("classpath_separator", CLASSPATH_SEPARATOR.to_string()),
+        ("classpath", classpath),
+        ("resolution_width", width),
+        ("resolution_height", height),
+        ("quickPlaySingleplayer", singleplayer),
+        ("quickPlayMultiplayer", multiplayer),
+        ("quickPlayRealms", realms),
+        (
+            "quickPlayPath",
+            ctx.quick_play_path.as_ref().map(path).unwrap_or_default(),
+        ),
+    ]);
+
+    let arguments = match &manifest.minecraft_arguments {
+        Some(legacy) if manifest.arguments.game.is_empty() => legacy_arguments(legacy),
+        _ => manifest.arguments.clone(),
+    };
+
+    let os = Os::current();
+    let features = launch_features(ctx);
+    let mut args = ctx.extra_jvm_args.clone();
+    args.extend(
+        arguments_for(&arguments.jvm, &os, &features)
+            .iter()
+            .map(|arg| substitute(arg, &values)),
+    );
+    // Saved to `assets/log_configs` along with the assets
+    let logging = &manifest.logging.client;
+    if !logging.argument.is_empty() && !logging.file.id.is_empty() {
+        let config = ctx.assets_root.join("log_configs").join(&logging.file.id);
+        args.push(logging.argument.replace("${path}", &path(&config)));
+    }
+    args.push(manifest.main_class.clone());
+    args.extend(
+        arguments_for(&arguments.game, &os, &features)
+            .iter()
+            .map(|arg| substitute(arg, &values)),
+    );
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use std::path::PathBuf;
+
+    use super::{
+        arguments_for, build_launch_args, launch_features, substitute, LaunchContext, QuickPlay,
+        Resolution,
+    };
+    use crate::manifest::{Features, JvmArgument, Manifest, Os};
+
+    fn os(name: &str, arch: &str) -> Os {
+        Os {
+            name: Some(name.to_string()),
+            arch: Some(arch.to_string()),
+            version: None,
+        }
+    }
+
+    #[test]
+    fn placeholders_are_substituted() {
+        let values = HashMap::from([("version_name", "1.20.1".to_string())]);
+
+        assert_eq!(
+            substitute("--version=${version_name}", &values),
+            "--version=1.20.1"
+        );
+        assert_eq!(substitute("${unknown}", &values), "${unknown}");
+    }
+
+    #[test]
+    fn substituted_values_are_not_expanded_again() {
+        let values = HashMap::from([
+            ("auth_player_name", "${auth_access_token}".to_string()),
+            ("auth_access_token", "secret".to_string()),
+        ]);
+
+        assert_eq!(
+            substitute("${auth_player_name} ${auth_access_token}", &values),
+            "${auth_access_token} secret"
+        );
+        assert_eq!(substitute("a ${unclosed", &values), "a ${unclosed");
+    }
+
+    #[test]
+    fn feature_gated_arguments() {
+        let args: Vec<JvmArgument> = serde_json::from_str(
+            r#"[
+                "--username",
+                {
+                    "rules": [{"action": "allow", "features": {"is_demo_user": true}}],
+                    "value": "--demo"
+                },
+                {
+                    "rules": [{"action": "allow", "features": {"has_custom_resolution": true}}],
+                    "value": ["--width", "${resolution_width}"]
+                }
+            ]"#,
+        )
+        .unwrap();
+        let features = Features {
+            is_demo_user: Some(true),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            arguments_for(&args, &os("linux", "x86_64"), &features),
+            ["--username", "--demo"]
+        );
+
+        let ctx = LaunchContext {
+            resolution: Some(Resolution {
+                width: 854,
+                height: 480,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            arguments_for(&args, &os("linux", "x86_64"), &launch_features(&ctx)),
+            ["--username", "--width", "${resolution_width}"]
+        );
+    }
+
+    #[test]
+    fn quick_play_enables_its_arguments() {
+        let args: Vec<JvmArgument> = serde_json::from_str(
+            r#"[
+                {
+                    "rules": [{"action": "allow", "features": {"has_quick_plays_support": true}}],
+                    "value": ["--quickPlayPath", "${quickPlayPath}"]
+                },
+                {
+                    "rules": [{"action": "allow", "features": {"is_quick_play_singleplayer": true}}],
+                    "value": ["--quickPlaySingleplayer", "${quickPlaySingleplayer}"]
+                },
+                {
+                    "rules": [{"action": "allow", "features": {"is_quick_play_multiplayer": true}}],
+                    "value": ["--quickPlayMultiplayer", "${quickPlayMultiplayer}"]
+                }
+            ]"#,
+        )
+        .unwrap();
+        let ctx = LaunchContext {
+            quick_play: Some(QuickPlay::Multiplayer("localhost:25565".to_string())),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            arguments_for(&args, &os("linux", "x86_64"), &launch_features(&ctx)),
+            ["--quickPlayMultiplayer", "${quickPlayMultiplayer}"]
+        );
+    }
+
+    #[test]
+    fn os_gated_arguments() {
+        let args: Vec<JvmArgument> = serde_json::from_str(
+            r#"[
+                {
+                    "rules": [{"action": "allow", "os": {"name": "osx"}}],
+                    "value": ["-XstartOnFirstThread"]
+                },
+                {
+                    "rules": [{"action": "allow", "os": {"arch": "x86"}}],
+                    "value": "-Xss1M"
+                }
+            ]"#,
+        )
+        .unwrap();
+        let features = Features::default();
+
+        assert_eq!(
+            arguments_for(&args, &os("osx", "arm64"), &features),
+            ["-XstartOnFirstThread"]
+        );
+        assert_eq!(
+            arguments_for(&args, &os("windows", "x86"), &features),
+            ["-Xss1M"]
+        );
+        assert!(arguments_for(&args, &os("linux", "x86_64"), &features).is_empty());
+    }
+
+    fn legacy_manifest(id: &str, assets: &str, minecraft_arguments: &str) -> Manifest {
+        let file = serde_json::json!({"path": null, "sha1": "", "size": 0, "url": ""});
+        serde_json::from_value(serde_json::json!({
+            "minecraftArguments": minecraft_arguments,
+            "assetIndex": {"id": assets, "sha1": "", "size": 0, "totalSize": 0, "url": ""},
+            "assets": assets,
+            "complianceLevel": 0,
+            "downloads": {"client": file, "server": file},
+            "id": id,
+            "javaVersion": {"component": "jre-legacy", "majorVersion": 8},
+            "libraries": [],
+            "logging": {"client": {"argument": "", "file": {"id": "", "sha1": "", "size": 0, "url": ""}, "type": ""}},
+            "mainClass": "net.minecraft.client.main.Main",
+            "minimumLauncherVersion": 18,
+            "releaseTime": "",
+            "time": "",
+            "type": "release"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn legacy_arguments_are_split() {
+        let manifest = legacy_manifest(
+            "1.12.2",
+            "1.12",
+            "--username ${auth_player_name} --version ${version_name}",
+        );
+        let ctx = LaunchContext {
+            auth_player_name: "Steve".to_string(),
+            classpath: vec![PathBuf::from("client.jar")],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            build_launch_args(&manifest, &ctx),
+            [
+                "-Djava.library.path=",
+                "-cp",
+                "client.jar",
+                "net.minecraft.client.main.Main",
+                "--username",
+                "Steve",
+                "--version",
+                "1.12.2",
+            ]
+        );
+    }
+    #[test]
+    fn legacy_placeholders_are_all_filled() {
+        let ctx = LaunchContext {
+            auth_player_name: "Steve".to_string(),
+            auth_uuid: "uuid".to_string(),
+            auth_access_token: "token".to_string(),
+            game_directory: PathBuf::from("game"),
+            assets_root: PathBuf::from("assets"),
+            ..Default::default()
+        };
+        let versions = [
+            (
+                legacy_manifest(
+                    "1.12.2",
+                    "1.12",
+                    "--username ${auth_player_name} --version ${version_name} --gameDir ${game_directory} --assetsDir ${assets_root} --assetIndex ${assets_index_name} --uuid ${auth_uuid} --accessToken ${auth_access_token} --userType ${user_type} --versionType ${version_type}",
+                ),
+                None,
+            ),
+            (
+                legacy_manifest(
+                    "1.7.10",
+                    "1.7.10",
+                    "--username ${auth_player_name} --version ${version_name} --gameDir ${game_directory} --assetsDir ${assets_root} --assetIndex ${assets_index_name} --uuid ${auth_uuid} --accessToken ${auth_access_token} --userProperties ${user_properties} --userType ${user_type}",
+                ),
+                Some(("--userProperties", "{}".to_string())),
+            ),
+            (
+                legacy_manifest(
+                    "1.5.2",
+                    "pre-1.6",
+                    "${auth_player_name} ${auth_session} --gameDir ${game_directory} --assetsDir ${game_assets}",
+                ),
+                Some((
+                    "--assetsDir",
+                    PathBuf::from("game")
+                        .join("resources")
+                        .to_string_lossy()
+                        .to_string(),
+                )),
+            ),
+        ];
+
+        for (manifest, expected) in versions {
+            let args = build_launch_args(&manifest, &ctx);
+            assert!(args.iter().all(|arg| !arg.contains("${")), "{args:?}");
+            if let Some((name, value)) = expected {
+                let i = args.iter().position(|arg| arg == name).unwrap();
+                assert_eq!(args[i + 1], value);
+            }
+        }
+        let args = build_launch_args(
+            &legacy_manifest("1.5.2", "pre-1.6", "${auth_session}"),
+            &ctx,
+        );
+        assert_eq!(args.last().unwrap(), "token:token:uuid");
+    }
+}
@@ -1,29 +1,38 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Serialize, Deserialize)]
+use crate::manifest::VersionType;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LauncherManifestLatest {
     pub release: String,
     pub snapshot: String,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LauncherManifestVersion {
     pub id: String,
     #[serde(rename = "releaseTime")]
     pub release_time: String,
     pub time: String,
     pub url: String,
+    /// Hash of the version manifest at `url`, only in the v2 manifest.
+    #[serde(default)]
+    pub sha1: Option<String>,
+    /// `0` for the versions without the current player safety features, `1`
+    /// for the others. Only in the v2 manifest.
+    #[serde(rename = "complianceLevel", default)]
+    pub compliance_level: Option<u8>,
     #[serde(rename = "type")]
-    pub version_type: String,
+    pub version_type: VersionType,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LauncherManifest {
     pub latest: LauncherManifestLatest,
     pub versions: Vec<LauncherManifestVersion>,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FabricVersionManifest {
     pub id: String,
     #[serde(rename = "releaseTime")]
@@ -33,7 +42,7 @@ pub struct FabricVersionManifest {
     pub version_type: String,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FabricLoaderInfo {
     pub separator: String,
     pub build: i32,
@@ -42,7 +51,68 @@ pub struct FabricLoaderInfo {
     pub stable: bool,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FabricLoaderManifest {
     pub loader: FabricLoaderInfo,
 }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuiltLoaderInfo {
+    pub separator: String,
+    pub build: i32,
+    pub maven: String,
+    pub version: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuiltLoaderManifest {
+    pub loader: QuiltLoaderInfo,
+}
+
+#[cfg(feature = "chrono")]
+impl LauncherManifestVersion {
+    pub fn release_time_utc(&self) -> Result<chrono::DateTime<chrono::Utc>, chrono::ParseError> {
+        crate::manifest::parse_time(&self.release_time)
+    }
+
+    pub fn time_utc(&self) -> Result<chrono::DateTime<chrono::Utc>, chrono::ParseError> {
+        crate::manifest::parse_time(&self.time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LauncherManifestVersion;
+
+    #[test]
+    fn v1_and_v2_versions() {
+        let v1: LauncherManifestVersion = serde_json::from_str(
+            r#"{"id": "1.20.1", "type": "release", "url": "https://example.com/1.20.1.json", "time": "", "releaseTime": ""}"#,
+        )
+        .unwrap();
+        assert_eq!(v1.sha1, None);
+        assert_eq!(v1.compliance_level, None);
+
+        let v2: LauncherManifestVersion = serde_json::from_str(
+            r#"{"id": "1.20.1", "type": "release", "url": "https://example.com/1.20.1.json", "time": "", "releaseTime": "", "sha1": "abc", "complianceLevel": 1}"#,
+        )
+        .unwrap();
+        assert_eq!(v2.sha1.as_deref(), Some("abc"));
+        assert_eq!(v2.compliance_level, Some(1));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn release_times_are_parsed() {
+        let version: LauncherManifestVersion = serde_json::from_str(
+            r#"{"id": "1.20.1", "type": "release", "url": "", "time": "not a time", "releaseTime": "2023-06-12T15:25:51+02:00"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            version.release_time_utc().unwrap().to_rfc3339(),
+            "2023-06-12T13:25:51+00:00"
+        );
+        assert!(version.time_utc().is_err());
+    }
+}
@@ -1,11 +1,15 @@
+#![allow(clippy::ptr_arg)]
+
 pub mod client;
 pub mod error;
 pub mod json_profiles;
+pub mod launch;
 pub mod launcher_manifest;
 pub mod manifest;
 
 pub mod prelude {
     pub use super::client::*;
     pub use super::error::*;
+    pub use super::launch::*;
     pub use super::manifest::*;
 }
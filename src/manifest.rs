@@ -1,11 +1,14 @@
-use std::{collections::HashMap, fs};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::error::ManifestError;
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct ManifestAssetIndex {
     pub id: String,
@@ -16,7 +19,21 @@ pub struct ManifestAssetIndex {
     pub url: String,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+/// An asset, stored as `assets/objects/{first two chars of hash}/{hash}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AssetObject {
+    pub hash: String,
+    pub size: u64,
+}
+
+/// The asset index a `ManifestAssetIndex` points to.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct AssetIndex {
+    /// The assets by their logical path, e.g. `icons/icon_16x16.png`.
+    pub objects: HashMap<String, AssetObject>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct ManifestComponent {
     pub component: String,
@@ -24,7 +41,17 @@ pub struct ManifestComponent {
     pub major_version: i8,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+/// Java 8, what the versions without a `javaVersion` run on.
+impl Default for ManifestComponent {
+    fn default() -> Self {
+        Self {
+            component: "jre-legacy".to_string(),
+            major_version: 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct ManifestFile {
     pub path: Option<String>,
@@ -33,7 +60,7 @@ pub struct ManifestFile {
     pub url: String,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ManifestDownloads {
     pub client: ManifestFile,
     pub client_mappings: Option<ManifestFile>,
@@ -41,7 +68,7 @@ pub struct ManifestDownloads {
     pub server_mappings: Option<ManifestFile>,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct ManifestRule {
     pub action: String,
@@ -49,21 +76,30 @@ pub struct ManifestRule {
     pub features: Option<HashMap<String, Value>>,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct ManifestLibraryDownloads {
     pub artifact: Option<ManifestFile>,
+    pub classifiers: Option<HashMap<String, ManifestFile>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestLibraryExtract {
+    #[serde(default)]
+    pub exclude: Vec<String>,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct ManifestLibrary {
     pub downloads: ManifestLibraryDownloads,
     pub name: String,
     pub rules: Option<Vec<ManifestRule>>,
+    pub natives: Option<HashMap<String, String>>,
+    pub extract: Option<ManifestLibraryExtract>,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct FabricManifestLibrary {
     pub name: String,
@@ -72,34 +108,39 @@ pub struct FabricManifestLibrary {
     pub size: Option<u64>,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Rules {
     pub action: String,
     pub features: Option<Features>,
     pub os: Option<Os>,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Default, Deserialize, Serialize)]
 pub struct Features {
     pub is_demo_user: Option<bool>,
     pub has_custom_resolution: Option<bool>,
+    pub has_quick_plays_support: Option<bool>,
+    pub is_quick_play_singleplayer: Option<bool>,
+    pub is_quick_play_multiplayer: Option<bool>,
     pub is_quick_play_realms: Option<bool>,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Os {
     pub arch: Option<String>,
     pub name: Option<String>,
     pub version: Option<String>,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Default, Deserialize, Serialize)]
 pub struct Arguments {
+    #[serde(default)]
     pub game: Vec<JvmArgument>,
+    #[serde(default)]
     pub jvm: Vec<JvmArgument>,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[serde(untagged)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub enum JvmArgument {
@@ -119,12 +160,12 @@ pub enum VersionType {
     OldAlpha,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Default, Deserialize, Serialize)]
 pub struct Logging {
     pub client: ClientLogging,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Default, Deserialize, Serialize)]
 pub struct ClientLogging {
     pub argument: String,
     pub file: ClientLogFile,
@@ -132,7 +173,7 @@ pub struct ClientLogging {
     pub log_type: String,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Default, Deserialize, Serialize)]
 pub struct ClientLogFile {
     pub id: String,
     pub sha1: String,
@@ -140,24 +181,37 @@ pub struct ClientLogFile {
     pub url: String,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct Manifest {
+    /// Empty for the versions before 1.13, which have `minecraft_arguments`
+    /// instead.
+    #[serde(default)]
     pub arguments: Arguments,
+    /// The game arguments of the versions before 1.13, as one string.
+    #[serde(
+        rename = "minecraftArguments",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub minecraft_arguments: Option<String>,
     #[serde(rename = "assetIndex")]
     pub asset_index: ManifestAssetIndex,
     pub assets: String,
-    #[serde(rename = "complianceLevel")]
+    #[serde(rename = "complianceLevel", default)]
     pub compliance_level: i8,
     pub downloads: ManifestDownloads,
     pub id: String,
-    #[serde(rename = "javaVersion")]
+    /// Java 8 when the version doesn't say.
+    #[serde(rename = "javaVersion", default)]
     pub java_version: ManifestComponent,
     pub libraries: Vec<ManifestLibrary>,
+    /// Empty for the versions without a logging configuration.
+    #[serde(default)]
     pub logging: Logging,
     #[serde(rename = "mainClass")]
     pub main_class: String,
-    #[serde(rename = "minimumLauncherVersion")]
+    #[serde(rename = "minimumLauncherVersion", default)]
     pub minimum_launcher_version: i8,
     #[serde(rename = "releaseTime")]
     pub release_time: String,
@@ -166,7 +220,7 @@ pub struct Manifest {
     pub type_: VersionType,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct FabricManifest {
     pub arguments: Arguments,
@@ -180,18 +234,197 @@ pub struct FabricManifest {
     pub type_: VersionType,
 }
 
-fn maven_to_path(coordinate: &str) -> String {
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct ForgeManifestLibrary {
+    pub name: String,
+    pub downloads: Option<ManifestLibraryDownloads>,
+    pub url: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ForgeManifestDownloads {
+    pub client: Option<ManifestFile>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct ForgeManifest {
+    pub arguments: Option<Arguments>,
+    pub downloads: Option<ForgeManifestDownloads>,
+    pub inherits_from: String,
+    pub id: String,
+    pub libraries: Vec<ForgeManifestLibrary>,
+    pub main_class: String,
+    pub release_time: String,
+    pub time: String,
+    #[serde(rename = "type")]
+    pub type_: VersionType,
+}
+
+/// A library of a version json that inherits from another one: a full
+/// library when it has `downloads`, only a maven coordinate otherwise.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum InheritingLibrary {
+    Full(ManifestLibrary),
+    Coordinate(ForgeManifestLibrary),
+}
+
+/// A version json that only lists what differs from the version it
+/// `inheritsFrom`, e.g. the ones of modpacks and custom launchers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InheritingManifest {
+    pub id: String,
+    pub inherits_from: String,
+    #[serde(default)]
+    pub arguments: Option<Arguments>,
+    #[serde(default)]
+    pub downloads: Option<ForgeManifestDownloads>,
+    #[serde(default)]
+    pub libraries: Vec<InheritingLibrary>,
+    #[serde(default)]
+    pub minecraft_arguments: Option<String>,
+    #[serde(default)]
+    pub main_class: Option<String>,
+    #[serde(default)]
+    pub release_time: Option<String>,
+    #[serde(default)]
+    pub time: Option<String>,
+    #[serde(default, rename = "type")]
+    pub type_: Option<VersionType>,
+}
+
+/// A value of the `data` of an install profile, for each side.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ForgeSidedData {
+    pub client: String,
+    pub server: String,
+}
+
+/// A program the installer runs after the download, e.g. to patch the
+/// vanilla jar.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ForgeProcessor {
+    /// The sides it runs for, all of them when missing.
+    #[serde(default)]
+    pub sides: Option<Vec<String>>,
+    /// The maven coordinate of the processor jar.
+    pub jar: String,
+    #[serde(default)]
+    pub classpath: Vec<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// The files it creates, with their sha1.
+    #[serde(default)]
+    pub outputs: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct ForgeInstallProfile {
+    pub minecraft: String,
+    pub libraries: Vec<ForgeManifestLibrary>,
+    #[serde(default)]
+    pub data: HashMap<String, ForgeSidedData>,
+    #[serde(default)]
+    pub processors: Vec<ForgeProcessor>,
+}
+
+const MOJANG_LIBRARIES_URL: &str = "https://libraries.minecraft.net/";
+
+/// Converts a `group:artifact:version[:classifier]` coordinate to the path of
+/// its jar in a maven repository.
+///
+/// The path is `/` separated whatever the platform, it is also the path of
+/// the jar in the repository url.
+pub(crate) fn maven_to_path(coordinate: &str) -> Result<String, ManifestError> {
     let parts: Vec<&str> = coordinate.split(':').collect();
-    if parts.len() != 3 {
-        panic!("Invalid format");
+    if parts.iter().any(|p| p.is_empty()) {
+        return Err(ManifestError::InvalidCoordinate(coordinate.to_string()));
+    }
+
+    let (group, artifact, version, classifier) = match parts[..] {
+        [group, artifact, version] => (group, artifact, version, String::new()),
+        [group, artifact, version, classifier] => {
+            (group, artifact, version, format!("-{classifier}"))
+        }
+        _ => return Err(ManifestError::InvalidCoordinate(coordinate.to_string())),
+    };
+    let group = group.replace('.', "/");
+
+    Ok(format!(
+        "{}/{}/{}/{}-{}{}.jar",
+        group, artifact, version, artifact, version, classifier
+    ))
+}
+
+/// The url of `path` in the maven `repository`, which may or may not end
+/// with a slash.
+fn maven_url(repository: &str, path: &str) -> String {
+    format!("{}/{}", repository.trim_end_matches('/'), path)
+}
+
+/// Libraries and arguments to layer on top of a `Manifest`, e.g. the extra
+/// libraries of a modpack.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialManifest {
+    #[serde(default)]
+    pub libraries: Vec<ManifestLibrary>,
+    #[serde(default)]
+    pub arguments: Option<Arguments>,
+    /// Replaces the main class of the base when set.
+    #[serde(default)]
+    pub main_class: Option<String>,
+}
+
+/// The coordinate of a library without its version, `group:artifact` plus
+/// the classifier if it has one.
+fn library_key(name: &str) -> String {
+    let mut parts: Vec<&str> = name.split(':').collect();
+    if parts.len() > 2 {
+        parts.remove(2);
+    }
+    parts.join(":")
+}
+
+/// `overlay` followed by the libraries of `base` it has no version of.
+fn merge_libraries(
+    overlay: Vec<ManifestLibrary>,
+    base: &[ManifestLibrary],
+) -> Vec<ManifestLibrary> {
+    let overridden: HashSet<String> = overlay.iter().map(|lib| library_key(&lib.name)).collect();
+
+    let mut libraries = overlay;
+    libraries.extend(
+        base.iter()
+            .filter(|lib| !overridden.contains(&library_key(&lib.name)))
+            .cloned(),
+    );
+    libraries
+}
+
+/// Layers `overlay` on top of `base`: its libraries replace the versions of
+/// the same `group:artifact` in the base and its arguments come after the
+/// base ones.
+pub fn merge_manifests(base: &Manifest, overlay: &PartialManifest) -> Manifest {
+    let mut arguments = base.arguments.clone();
+    if let Some(overlay_arguments) = &overlay.arguments {
+        arguments.game.extend(overlay_arguments.game.clone());
+        arguments.jvm.extend(overlay_arguments.jvm.clone());
+    }
+
+    Manifest {
+        arguments,
+        libraries: merge_libraries(overlay.libraries.clone(), &base.libraries),
+        main_class: overlay
+            .main_class
+            .clone()
+            .unwrap_or_else(|| base.main_class.clone()),
+        ..base.clone()
     }
-    let group = parts[0].replace('.', "/");
-    let artifact = parts[1];
-    let version = parts[2];
-    format!(
-        "{}/{}/{}/{}-{}.jar",
-        group, artifact, version, artifact, version
-    )
 }
 
 pub fn manifest_from_fabric(
@@ -202,27 +435,32 @@ pub fn manifest_from_fabric(
         .libraries
         .into_iter()
         .map(|lib| {
-            let path = maven_to_path(&lib.name.clone());
+            let path = maven_to_path(&lib.name)?;
             let sha1 = lib.sha1.unwrap_or_else(|| "".to_string());
-            let size = lib.size.unwrap_or(1_i64 as u64);
+            // Most profiles don't list the size, 0 keeps it unknown
+            let size = lib.size.unwrap_or_default();
 
-            ManifestLibrary {
+            Ok(ManifestLibrary {
                 name: lib.name.clone(),
                 downloads: ManifestLibraryDownloads {
                     artifact: Some(ManifestFile {
+                        url: maven_url(&lib.url, &path),
                         path: Some(path),
-                        sha1: sha1,
-                        size: size,
-                        url: format!("{}{}", lib.url, maven_to_path(&lib.name)),
+                        sha1,
+                        size,
                     }),
+                    classifiers: None,
                 },
                 rules: None,
-            }
+                natives: None,
+                extract: None,
+            })
         })
-        .collect();
+        .collect::<Result<_, ManifestError>>()?;
 
-    let mut combined_libraries = fabric_libraries;
-    combined_libraries.extend(base_manifest.libraries.clone());
+    // Fabric often ships newer versions of vanilla libraries (e.g. ASM),
+    // keeping both would put two versions on the classpath
+    let combined_libraries = merge_libraries(fabric_libraries, &base_manifest.libraries);
 
     let mut combined_game_args = base_manifest.arguments.game.clone();
     combined_game_args.extend(fabric_manifest.arguments.game);
@@ -244,6 +482,158 @@ pub fn manifest_from_fabric(
     })
 }
 
+/// A library of a loader or custom version json, which may only have a
+/// maven coordinate.
+fn forge_library(lib: ForgeManifestLibrary) -> Result<ManifestLibrary, ManifestError> {
+    // Older installers only ship the maven coordinate (and sometimes a
+    // repository url), so the artifact has to be derived from it.
+    let artifact = match lib.downloads.and_then(|d| d.artifact) {
+        Some(artifact) => artifact,
+        None => {
+            let path = maven_to_path(&lib.name)?;
+            let repository = lib.url.unwrap_or_else(|| MOJANG_LIBRARIES_URL.to_string());
+
+            ManifestFile {
+                url: maven_url(&repository, &path),
+                path: Some(path),
+                sha1: String::new(),
+                size: 0,
+            }
+        }
+    };
+
+    Ok(ManifestLibrary {
+        name: lib.name,
+        downloads: ManifestLibraryDownloads {
+            artifact: Some(artifact),
+            classifiers: None,
+        },
+        rules: None,
+        natives: None,
+        extract: None,
+    })
+}
+
+pub fn manifest_from_forge(
+    forge_manifest: ForgeManifest,
+    base_manifest: &mut Manifest,
+) -> Result<Manifest, ManifestError> {
+    let forge_libraries: Vec<ManifestLibrary> = forge_manifest
+        .libraries
+        .into_iter()
+        .map(forge_library)
+        .collect::<Result<_, ManifestError>>()?;
+
+    // Forge replaces some vanilla libraries with newer versions, keeping
+    // both would put two versions on the classpath
+    let combined_libraries = merge_libraries(forge_libraries, &base_manifest.libraries);
+
+    let mut arguments = base_manifest.arguments.clone();
+    if let Some(forge_arguments) = forge_manifest.arguments {
+        arguments.game.extend(forge_arguments.game);
+        arguments.jvm.extend(forge_arguments.jvm);
+    }
+
+    // Keep the vanilla client unless the loader ships a replacement for it.
+    let mut downloads = base_manifest.downloads.clone();
+    if let Some(client) = forge_manifest.downloads.and_then(|d| d.client) {
+        downloads.client = client;
+    }
+
+    Ok(Manifest {
+        arguments,
+        downloads,
+        libraries: combined_libraries,
+        main_class: forge_manifest.main_class,
+        release_time: forge_manifest.release_time,
+        time: forge_manifest.time,
+        type_: forge_manifest.type_,
+        ..base_manifest.clone()
+    })
+}
+
+/// Layers a version json on top of the `parent` it inherits from: its
+/// libraries replace the versions of the same `group:artifact` in the
+/// parent, its arguments come after the parent ones and everything else it
+/// sets replaces the parent value.
+pub fn manifest_from_inheriting(
+    manifest: InheritingManifest,
+    parent: &Manifest,
+) -> Result<Manifest, ManifestError> {
+    let libraries: Vec<ManifestLibrary> = manifest
+        .libraries
+        .into_iter()
+        .map(|lib| match lib {
+            InheritingLibrary::Full(lib) => Ok(lib),
+            InheritingLibrary::Coordinate(lib) => forge_library(lib),
+        })
+        .collect::<Result<_, ManifestError>>()?;
+
+    let mut arguments = parent.arguments.clone();
+    if let Some(child_arguments) = manifest.arguments {
+        arguments.game.extend(child_arguments.game);
+        arguments.jvm.extend(child_arguments.jvm);
+    }
+
+    let mut downloads = parent.downloads.clone();
+    if let Some(client) = manifest.downloads.and_then(|d| d.client) {
+        downloads.client = client;
+    }
+
+    Ok(Manifest {
+        arguments,
+        downloads,
+        id: manifest.id,
+        libraries: merge_libraries(libraries, &parent.libraries),
+        // The legacy arguments are a single string, they can only be replaced
+        minecraft_arguments: manifest
+            .minecraft_arguments
+            .or_else(|| parent.minecraft_arguments.clone()),
+        main_class: manifest
+            .main_class
+            .unwrap_or_else(|| parent.main_class.clone()),
+        release_time: manifest
+            .release_time
+            .unwrap_or_else(|| parent.release_time.clone()),
+        time: manifest.time.unwrap_or_else(|| parent.time.clone()),
+        type_: manifest.type_.unwrap_or_else(|| parent.type_.clone()),
+        ..parent.clone()
+    })
+}
+
+/// The libraries of the install profile that `manifest` doesn't list, the
+/// ones only its processors use.
+pub fn processor_libraries(
+    profile: &ForgeInstallProfile,
+    manifest: &Manifest,
+) -> Result<Vec<ManifestLibrary>, ManifestError> {
+    profile
+        .libraries
+        .iter()
+        .filter(|lib| {
+            !manifest
+                .libraries
+                .iter()
+                .any(|existing| existing.name == lib.name)
+        })
+        .cloned()
+        .map(forge_library)
+        .collect()
+}
+
+/// Like `manifest_from_forge`, also returning the libraries only the
+/// processors of the install profile use. They are downloaded but never put
+/// on the classpath, so they aren't part of the manifest.
+pub fn manifest_from_neoforge(
+    neoforge_manifest: ForgeManifest,
+    install_profile: &ForgeInstallProfile,
+    base_manifest: &mut Manifest,
+) -> Result<(Manifest, Vec<ManifestLibrary>), ManifestError> {
+    let manifest = manifest_from_forge(neoforge_manifest, base_manifest)?;
+    let libraries = processor_libraries(install_profile, &manifest)?;
+    Ok((manifest, libraries))
+}
+
 pub fn read_manifest_from_str(string: &str) -> Result<Manifest, ManifestError> {
     let manifest: Manifest = serde_json::from_str(string)?;
     Ok(manifest)
@@ -255,12 +645,145 @@ pub fn read_manifest_from_file(file: &str) -> Result<Manifest, ManifestError> {
     Ok(manifest)
 }
 
-impl ToString for VersionType {
-    fn to_string(&self) -> String {
-        match *self {
-            VersionType::Release => String::from("Release"),
-            VersionType::Snapshot => String::from("Snapshot"),
-            VersionType::OldAlpha | VersionType::OldBeta => String::from("Old"),
+/// Parses an ISO-8601 time of the manifests, e.g. `2023-06-12T13:25:51+00:00`.
+#[cfg(feature = "chrono")]
+pub(crate) fn parse_time(time: &str) -> Result<chrono::DateTime<chrono::Utc>, chrono::ParseError> {
+    chrono::DateTime::parse_from_rfc3339(time).map(|time| time.with_timezone(&chrono::Utc))
+}
+
+#[cfg(feature = "chrono")]
+impl Manifest {
+    pub fn release_time_utc(&self) -> Result<chrono::DateTime<chrono::Utc>, chrono::ParseError> {
+        parse_time(&self.release_time)
+    }
+
+    pub fn time_utc(&self) -> Result<chrono::DateTime<chrono::Utc>, chrono::ParseError> {
+        parse_time(&self.time)
+    }
+}
+
+impl Os {
+    /// The current platform, as used in the manifest rules.
+    pub fn current() -> Self {
+        Self {
+            arch: Some(current_os_arch().to_string()),
+            name: Some(current_os_name().to_string()),
+            version: None,
+        }
+    }
+}
+
+/// The operating system name as used in the manifest rules.
+pub fn current_os_name() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "osx",
+        os => os,
+    }
+}
+
+/// The architecture name as used in the manifest rules.
+pub fn current_os_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "aarch64" => "arm64",
+        arch => arch,
+    }
+}
+
+fn rule_applies(rule: &ManifestRule, os_name: &str, os_arch: &str) -> bool {
+    // Feature gated rules only make sense for arguments, never for libraries.
+    if rule.features.is_some() {
+        return false;
+    }
+
+    let Some(os) = &rule.os else { return true };
+    os.get("name").is_none_or(|name| name == os_name)
+        && os.get("arch").is_none_or(|arch| arch == os_arch)
+}
+
+fn library_allowed(lib: &ManifestLibrary, os_name: &str, os_arch: &str) -> bool {
+    let Some(rules) = &lib.rules else { return true };
+
+    // Without a matching rule the library is disallowed, the last matching
+    // rule decides otherwise.
+    rules
+        .iter()
+        .rfind(|rule| rule_applies(rule, os_name, os_arch))
+        .is_some_and(|rule| rule.action == "allow")
+}
+
+/// Evaluates the library `rules` against the current platform.
+pub fn should_download_library(lib: &ManifestLibrary) -> bool {
+    library_allowed(lib, current_os_name(), current_os_arch())
+}
+
+fn native_file(lib: &ManifestLibrary, os_name: &str, os_arch: &str) -> Option<ManifestFile> {
+    if !library_allowed(lib, os_name, os_arch) {
+        return None;
+    }
+
+    // Older manifests list the natives as classifiers of the library, the
+    // classifier may contain the `${arch}` placeholder for the pointer width.
+    if let Some(natives) = &lib.natives {
+        let classifier = natives.get(os_name)?.replace(
+            "${arch}",
+            match os_arch {
+                "x86" => "32",
+                _ => "64",
+            },
+        );
+        return lib
+            .downloads
+            .classifiers
+            .as_ref()?
+            .get(&classifier)
+            .cloned();
+    }
+
+    // Newer manifests ship every native as its own library, qualified by a
+    // `natives-{os}[-{arch}]` classifier.
+    let classifier = lib.name.split(':').nth(3)?;
+    let platform = classifier.strip_prefix("natives-")?;
+    let arch_matches = match platform.rsplit_once('-') {
+        Some((_, "arm64")) => os_arch == "arm64",
+        Some((_, "x86")) => os_arch == "x86",
+        _ => os_arch != "arm64" && os_arch != "x86",
+    };
+    if !arch_matches {
+        return None;
+    }
+
+    lib.downloads.artifact.clone()
+}
+
+/// Returns the native jar of a library for the current platform, if any.
+pub fn native_library_file(lib: &ManifestLibrary) -> Option<ManifestFile> {
+    native_file(lib, current_os_name(), current_os_arch())
+}
+
+impl std::fmt::Display for VersionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                VersionType::Release => "Release",
+                VersionType::Snapshot => "Snapshot",
+                VersionType::OldAlpha | VersionType::OldBeta => "Old",
+            }
+        )
+    }
+}
+
+impl std::str::FromStr for VersionType {
+    type Err = ManifestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "release" => Ok(VersionType::Release),
+            "snapshot" => Ok(VersionType::Snapshot),
+            "old_beta" => Ok(VersionType::OldBeta),
+            "old_alpha" => Ok(VersionType::OldAlpha),
+            _ => Err(ManifestError::UnknownVersionType(s.to_string())),
         }
     }
 }
@@ -268,8 +791,41 @@ impl ToString for VersionType {
 #[cfg(test)]
 mod tests {
     use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    use super::{
+        library_allowed, manifest_from_fabric, manifest_from_forge, manifest_from_inheriting,
+        manifest_from_neoforge, maven_to_path, maven_url, merge_libraries, merge_manifests,
+        native_file, AssetIndex, FabricManifest, ForgeInstallProfile, ForgeManifest,
+        InheritingManifest, Manifest, ManifestLibrary, PartialManifest, VersionType,
+    };
+    use crate::error::ManifestError;
 
-    use super::VersionType;
+    fn base_manifest(libraries: &[&str]) -> Manifest {
+        let file = |path: &str| json!({"path": path, "sha1": "", "size": 1, "url": ""});
+        let libraries: Vec<_> = libraries
+            .iter()
+            .map(|name| json!({"downloads": {"artifact": file(name)}, "name": name}))
+            .collect();
+
+        serde_json::from_value(json!({
+            "arguments": {"game": ["--username", "${auth_player_name}"], "jvm": []},
+            "assetIndex": {"id": "5", "sha1": "", "size": 1, "totalSize": 1, "url": ""},
+            "assets": "5",
+            "complianceLevel": 1,
+            "downloads": {"client": file(""), "server": file("")},
+            "id": "1.20.1",
+            "javaVersion": {"component": "java-runtime-gamma", "majorVersion": 17},
+            "libraries": libraries,
+            "logging": {"client": {"argument": "", "file": {"id": "", "sha1": "", "size": 1, "url": ""}, "type": ""}},
+            "mainClass": "net.minecraft.client.main.Main",
+            "minimumLauncherVersion": 21,
+            "releaseTime": "",
+            "time": "",
+            "type": "release"
+        }))
+        .unwrap()
+    }
 
     #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
     #[serde(rename_all(deserialize = "camelCase"))]
@@ -302,6 +858,22 @@ mod tests {
         assert_eq!(json.unwrap(), expected_json);
     }
 
+    #[test]
+    fn version_type_from_str() {
+        assert_eq!(
+            "release".parse::<VersionType>().unwrap(),
+            VersionType::Release
+        );
+        assert_eq!(
+            "old_alpha".parse::<VersionType>().unwrap(),
+            VersionType::OldAlpha
+        );
+        assert!(matches!(
+            "pending".parse::<VersionType>(),
+            Err(ManifestError::UnknownVersionType(_))
+        ));
+    }
+
     #[test]
     fn version_type_deserialize() {
         let raw_json = r#"{"type":"old_beta"}"#;
@@ -313,4 +885,389 @@ mod tests {
         assert!(json.is_ok());
         assert_eq!(json.unwrap(), expected_st);
     }
+
+    fn library_with_rules(rules: &str) -> ManifestLibrary {
+        serde_json::from_str(&format!(
+            r#"{{"downloads":{{}},"name":"org.lwjgl:lwjgl:3.3.1","rules":{rules}}}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn library_without_rules_is_allowed() {
+        let lib: ManifestLibrary =
+            serde_json::from_str(r#"{"downloads":{},"name":"org.lwjgl:lwjgl:3.3.1"}"#).unwrap();
+
+        assert!(library_allowed(&lib, "linux", "x86_64"));
+    }
+
+    #[test]
+    fn library_allowed_only_on_matching_os() {
+        let lib = library_with_rules(r#"[{"action":"allow","os":{"name":"osx"}}]"#);
+
+        assert!(library_allowed(&lib, "osx", "x86_64"));
+        assert!(!library_allowed(&lib, "linux", "x86_64"));
+    }
+
+    #[test]
+    fn library_disallowed_on_matching_os() {
+        let lib =
+            library_with_rules(r#"[{"action":"allow"},{"action":"disallow","os":{"name":"osx"}}]"#);
+
+        assert!(library_allowed(&lib, "windows", "x86_64"));
+        assert!(!library_allowed(&lib, "osx", "x86_64"));
+    }
+
+    #[test]
+    fn library_allowed_only_on_matching_arch() {
+        let lib =
+            library_with_rules(r#"[{"action":"allow","os":{"name":"windows","arch":"x86"}}]"#);
+
+        assert!(library_allowed(&lib, "windows", "x86"));
+        assert!(!library_allowed(&lib, "windows", "x86_64"));
+    }
+
+    #[test]
+    fn native_file_from_classifiers() {
+        let lib: ManifestLibrary = serde_json::from_str(
+            r#"{
+                "downloads": {
+                    "classifiers": {
+                        "natives-windows-32": {"path": "w32.jar", "sha1": "", "size": 0, "url": ""},
+                        "natives-windows-64": {"path": "w64.jar", "sha1": "", "size": 0, "url": ""}
+                    }
+                },
+                "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4",
+                "natives": {"windows": "natives-windows-${arch}"}
+            }"#,
+        )
+        .unwrap();
+
+        let file = native_file(&lib, "windows", "x86_64").unwrap();
+        assert_eq!(file.path.as_deref(), Some("w64.jar"));
+        let file = native_file(&lib, "windows", "x86").unwrap();
+        assert_eq!(file.path.as_deref(), Some("w32.jar"));
+        assert!(native_file(&lib, "linux", "x86_64").is_none());
+    }
+
+    #[test]
+    fn native_file_from_classified_library() {
+        let lib: ManifestLibrary = serde_json::from_str(
+            r#"{
+                "downloads": {
+                    "artifact": {"path": "arm.jar", "sha1": "", "size": 0, "url": ""}
+                },
+                "name": "org.lwjgl:lwjgl:3.3.1:natives-macos-arm64",
+                "rules": [{"action": "allow", "os": {"name": "osx"}}]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(native_file(&lib, "osx", "arm64").is_some());
+        assert!(native_file(&lib, "osx", "x86_64").is_none());
+        assert!(native_file(&lib, "linux", "arm64").is_none());
+    }
+
+    #[test]
+    fn maven_coordinates_to_paths() {
+        assert_eq!(
+            maven_to_path("net.fabricmc:intermediary:1.20.1").unwrap(),
+            "net/fabricmc/intermediary/1.20.1/intermediary-1.20.1.jar"
+        );
+        assert_eq!(
+            maven_to_path("org.lwjgl:lwjgl:3.3.1:natives-linux").unwrap(),
+            "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.jar"
+        );
+        assert!(matches!(
+            maven_to_path("org.lwjgl:lwjgl"),
+            Err(ManifestError::InvalidCoordinate(_))
+        ));
+        assert!(matches!(
+            maven_to_path("org.lwjgl::3.3.1"),
+            Err(ManifestError::InvalidCoordinate(_))
+        ));
+    }
+
+    #[test]
+    fn classified_maven_url() {
+        let path = maven_to_path("net.fabricmc:tiny-mappings-parser:0.3.0:fat").unwrap();
+        assert_eq!(
+            maven_url("https://maven.fabricmc.net/", &path),
+            "https://maven.fabricmc.net/net/fabricmc/tiny-mappings-parser/0.3.0/tiny-mappings-parser-0.3.0-fat.jar"
+        );
+        assert_eq!(
+            maven_url("https://maven.fabricmc.net", &path),
+            maven_url("https://maven.fabricmc.net/", &path)
+        );
+    }
+
+    #[test]
+    fn malformed_asset_index_is_an_error() {
+        let index: AssetIndex = serde_json::from_str(
+            r#"{"objects": {"icons/icon_16x16.png": {"hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3665}}}"#,
+        )
+        .unwrap();
+        assert_eq!(index.objects["icons/icon_16x16.png"].size, 3665);
+
+        assert!(
+            serde_json::from_str::<AssetIndex>(r#"{"objects": {"a.ogg": {"size": 1}}}"#).is_err()
+        );
+        assert!(serde_json::from_str::<AssetIndex>(r#"{"objects": []}"#).is_err());
+    }
+
+    #[test]
+    fn overlay_libraries_replace_other_versions() {
+        let libraries = |names: &[&str]| -> Vec<ManifestLibrary> {
+            names
+                .iter()
+                .map(|name| {
+                    serde_json::from_str(&format!(r#"{{"name": "{name}", "downloads": {{}}}}"#))
+                        .unwrap()
+                })
+                .collect()
+        };
+        let base = libraries(&[
+            "org.ow2.asm:asm:9.3",
+            "org.lwjgl:lwjgl:3.3.1",
+            "org.lwjgl:lwjgl:3.3.1:natives-linux",
+        ]);
+
+        let merged = merge_libraries(
+            libraries(&["org.ow2.asm:asm:9.6", "org.lwjgl:lwjgl:3.3.3"]),
+            &base,
+        );
+        let names: Vec<&str> = merged.iter().map(|lib| lib.name.as_str()).collect();
+
+        assert_eq!(
+            names,
+            [
+                "org.ow2.asm:asm:9.6",
+                "org.lwjgl:lwjgl:3.3.3",
+                "org.lwjgl:lwjgl:3.3.1:natives-linux"
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_overlay_keeps_the_manifest() {
+        let base = base_manifest(&["org.ow2.asm:asm:9.3"]);
+
+        assert_eq!(merge_manifests(&base, &PartialManifest::default()), base);
+    }
+
+    #[test]
+    fn old_manifests_get_defaults() {
+        let file = json!({"sha1": "", "size": 0, "url": ""});
+        let manifest: Manifest = serde_json::from_value(json!({
+            "minecraftArguments": "${auth_player_name} ${auth_session}",
+            "assetIndex": {"id": "pre-1.6", "sha1": "", "size": 0, "totalSize": 0, "url": ""},
+            "assets": "pre-1.6",
+            "downloads": {"client": file, "server": file},
+            "id": "a1.0.4",
+            "libraries": [],
+            "mainClass": "net.minecraft.launchwrapper.Launch",
+            "releaseTime": "2010-07-09T22:00:00+00:00",
+            "time": "2010-07-09T22:00:00+00:00",
+            "type": "old_alpha"
+        }))
+        .unwrap();
+
+        assert_eq!(manifest.java_version.major_version, 8);
+        assert_eq!(manifest.compliance_level, 0);
+        assert_eq!(manifest.minimum_launcher_version, 0);
+        assert!(manifest.logging.client.file.url.is_empty());
+    }
+
+    #[test]
+    fn fabric_libraries_replace_vanilla_versions() {
+        let mut base = base_manifest(&["org.ow2.asm:asm:9.3", "com.mojang:brigadier:1.1.8"]);
+        let fabric: FabricManifest = serde_json::from_value(json!({
+            "arguments": {"game": [], "jvm": ["-DFabricMcEmu= net.minecraft.client.main.Main "]},
+            "inheritsFrom": "1.20.1",
+            "id": "fabric-loader-0.14.21-1.20.1",
+            "libraries": [{"name": "org.ow2.asm:asm:9.5", "url": "https://maven.fabricmc.net/"}],
+            "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient",
+            "releaseTime": "",
+            "time": "",
+            "type": "release"
+        }))
+        .unwrap();
+
+        let manifest = manifest_from_fabric(fabric, &mut base).unwrap();
+        let names: Vec<&str> = manifest
+            .libraries
+            .iter()
+            .map(|lib| lib.name.as_str())
+            .collect();
+
+        assert_eq!(names, ["org.ow2.asm:asm:9.5", "com.mojang:brigadier:1.1.8"]);
+    }
+
+    #[test]
+    fn fabric_profile_intermediary_is_resolved() {
+        // Trimmed profile of fabric-loader-0.14.21 for 1.20.1
+        let fabric: FabricManifest = serde_json::from_str(
+            r#"{
+                "id": "fabric-loader-0.14.21-1.20.1",
+                "inheritsFrom": "1.20.1",
+                "releaseTime": "2023-07-05T18:46:17+0000",
+                "time": "2023-07-05T18:46:17+0000",
+                "type": "release",
+                "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient",
+                "arguments": {"game": [], "jvm": ["-DFabricMcEmu= net.minecraft.client.main.Main "]},
+                "libraries": [
+                    {
+                        "name": "org.ow2.asm:asm:9.5",
+                        "url": "https://maven.fabricmc.net/",
+                        "md5": "29721ee4b5eacf0a34b204c345c8bc69",
+                        "sha1": "dc6ea1875f4d64fbc85e1691c95b96a3d8569c90",
+                        "size": 121863
+                    },
+                    {"name": "net.fabricmc:intermediary:1.20.1", "url": "https://maven.fabricmc.net/"},
+                    {"name": "net.fabricmc:fabric-loader:0.14.21", "url": "https://maven.fabricmc.net/"}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let manifest = manifest_from_fabric(fabric, &mut base_manifest(&[])).unwrap();
+        let intermediary = manifest.libraries[1].downloads.artifact.as_ref().unwrap();
+
+        assert_eq!(
+            intermediary.url,
+            "https://maven.fabricmc.net/net/fabricmc/intermediary/1.20.1/intermediary-1.20.1.jar"
+        );
+        assert_eq!(
+            intermediary.path.as_deref(),
+            Some("net/fabricmc/intermediary/1.20.1/intermediary-1.20.1.jar")
+        );
+        assert_eq!(intermediary.size, 0);
+        assert_eq!(
+            manifest.libraries[0]
+                .downloads
+                .artifact
+                .as_ref()
+                .unwrap()
+                .size,
+            121863
+        );
+    }
+
+    #[test]
+    fn forge_libraries_replace_vanilla_versions() {
+        let mut base = base_manifest(&[
+            "org.ow2.asm:asm:9.3",
+            "org.lwjgl:lwjgl:3.3.1",
+            "org.lwjgl:lwjgl:3.3.1:natives-linux",
+        ]);
+        let forge: ForgeManifest = serde_json::from_value(json!({
+            "id": "1.20.1-forge-47.1.0",
+            "inheritsFrom": "1.20.1",
+            "libraries": [
+                {"name": "org.ow2.asm:asm:9.5", "url": "https://maven.minecraftforge.net/"},
+                {"name": "org.lwjgl:lwjgl:3.3.2", "url": "https://maven.minecraftforge.net/"}
+            ],
+            "mainClass": "cpw.mods.bootstraplauncher.BootstrapLauncher",
+            "releaseTime": "",
+            "time": "",
+            "type": "release"
+        }))
+        .unwrap();
+
+        let manifest = manifest_from_forge(forge, &mut base).unwrap();
+        let names: Vec<&str> = manifest
+            .libraries
+            .iter()
+            .map(|lib| lib.name.as_str())
+            .collect();
+
+        assert_eq!(
+            names,
+            [
+                "org.ow2.asm:asm:9.5",
+                "org.lwjgl:lwjgl:3.3.2",
+                "org.lwjgl:lwjgl:3.3.1:natives-linux"
+            ]
+        );
+    }
+
+    #[test]
+    fn neoforge_processor_libraries_stay_off_the_classpath() {
+        let neoforge: ForgeManifest = serde_json::from_value(json!({
+            "id": "neoforge-20.4.80-beta",
+            "inheritsFrom": "1.20.4",
+            "libraries": [{"name": "net.neoforged.fancymodloader:loader:2.0.7", "url": "https://maven.neoforged.net/releases/"}],
+            "mainClass": "cpw.mods.bootstraplauncher.BootstrapLauncher",
+            "releaseTime": "",
+            "time": "",
+            "type": "release"
+        }))
+        .unwrap();
+        let profile: ForgeInstallProfile = serde_json::from_value(json!({
+            "minecraft": "1.20.4",
+            "libraries": [
+                {"name": "net.neoforged.fancymodloader:loader:2.0.7"},
+                {"name": "net.neoforged.installertools:installertools:2.1.2", "url": "https://maven.neoforged.net/releases/"}
+            ]
+        }))
+        .unwrap();
+
+        let (manifest, processor_libraries) =
+            manifest_from_neoforge(neoforge, &profile, &mut base_manifest(&[])).unwrap();
+        let names = |libraries: &[ManifestLibrary]| {
+            libraries
+                .iter()
+                .map(|lib| lib.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(&manifest.libraries),
+            ["net.neoforged.fancymodloader:loader:2.0.7"]
+        );
+        assert_eq!(
+            names(&processor_libraries),
+            ["net.neoforged.installertools:installertools:2.1.2"]
+        );
+    }
+
+    #[test]
+    fn inherited_libraries_keep_their_rules_and_natives() {
+        let child: InheritingManifest = serde_json::from_value(json!({
+            "id": "pack",
+            "inheritsFrom": "1.20.1",
+            "libraries": [
+                {
+                    "name": "org.lwjgl:lwjgl-platform:2.9.4",
+                    "downloads": {"classifiers": {"natives-linux": {"path": "l.jar", "sha1": "", "size": 1, "url": "https://example.com/l.jar"}}},
+                    "natives": {"linux": "natives-linux"},
+                    "extract": {"exclude": ["META-INF/"]},
+                    "rules": [{"action": "allow", "os": {"name": "linux"}}]
+                },
+                {"name": "net.fabricmc:fabric-loader:0.14.22", "url": "https://maven.fabricmc.net/"}
+            ]
+        }))
+        .unwrap();
+
+        let manifest = manifest_from_inheriting(child, &base_manifest(&[])).unwrap();
+        let natives = &manifest.libraries[0];
+        assert!(natives.downloads.artifact.is_none());
+        assert!(natives.rules.is_some());
+        assert!(natives.natives.is_some());
+        assert!(natives.extract.is_some());
+        assert_eq!(
+            manifest.libraries[1].downloads.artifact.as_ref().unwrap().url,
+            "https://maven.fabricmc.net/net/fabricmc/fabric-loader/0.14.22/fabric-loader-0.14.22.jar"
+        );
+    }
+
+    #[test]
+    fn quilt_hashed_mappings_are_resolved() {
+        let path = maven_to_path("org.quiltmc:hashed:1.20.1").unwrap();
+
+        assert_eq!(
+            maven_url("https://maven.quiltmc.org/repository/release/", &path),
+            "https://maven.quiltmc.org/repository/release/org/quiltmc/hashed/1.20.1/hashed-1.20.1.jar"
+        );
+    }
 }